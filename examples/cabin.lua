@@ -0,0 +1,27 @@
+-- prompt: a small wooden cabin with a stone chimney
+-- tags: building, house, wood
+local cabin = Schematic(16, 16, 14)
+cabin:DefineMaterial("logs", { color = "8b5a2b", roughness = 0.9 })
+cabin:DefineMaterial("stone", { color = "808080", roughness = 1 })
+cabin:DefineMaterial("glass", { color = "a0d8ef", alpha = 0.4, roughness = 0.1 })
+
+-- Floor and log walls
+cabin:FillMaterial(1, 0, 1, 12, 0, 12, "stone")
+cabin:FillMaterial(1, 1, 1, 12, 6, 12, "logs")
+cabin:Carve(2, 1, 2, 11, 6, 11)
+
+-- Door and windows
+cabin:Carve(6, 1, 1, 7, 3, 1)
+cabin:FillMaterial(2, 3, 12, 4, 4, 12, "glass")
+cabin:FillMaterial(9, 3, 12, 11, 4, 12, "glass")
+
+-- Roof
+for i = 0, 5 do
+    cabin:Fill(1 + i, 7 + i, 0, 12 - i, 7 + i, 13, "5c3a1e")
+end
+
+-- Chimney through the roof
+cabin:FillMaterial(13, 0, 4, 14, 14, 5, "stone")
+cabin:Carve(13, 13, 4, 13, 14, 4)
+
+return cabin
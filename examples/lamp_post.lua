@@ -0,0 +1,13 @@
+-- prompt: a street lamp
+-- tags: light, street, material
+local lamp = Schematic(7, 20, 7)
+lamp:DefineMaterial("iron", { color = "2f2f2f", metallic = 1, roughness = 0.4 })
+lamp:DefineMaterial("light", { color = "ffe9a8", alpha = 0.8, emissive = 4 })
+
+lamp:FillMaterial(2, 0, 2, 4, 1, 4, "iron")
+lamp:FillMaterial(3, 2, 3, 3, 15, 3, "iron")
+lamp:FillMaterial(2, 16, 2, 4, 17, 4, "light")
+lamp:FillMaterial(1, 18, 1, 5, 18, 5, "iron")
+lamp:SetMaterial(3, 19, 3, "iron")
+
+return lamp
@@ -0,0 +1,28 @@
+-- prompt: a big oak tree
+-- tags: tree, nature, plant
+local tree = Schematic(25, 28, 25)
+
+tree:Cylinder(12, 0, 12, 2, 14, "6b4423")
+-- Branches
+tree:Line(12, 10, 12, 5, 17, 8, "6b4423")
+tree:Line(12, 11, 12, 19, 18, 15, "6b4423")
+
+-- Lumpy crown made of overlapping spheres, shaded with noise
+local leaves = { "2e7d32", "388e3c", "1b5e20" }
+local blobs = { { 12, 19, 12, 7 }, { 6, 18, 8, 5 }, { 18, 19, 15, 5 }, { 12, 22, 16, 5 } }
+for i = 1, #blobs do
+    local x, y, z, r = blobs[i][1], blobs[i][2], blobs[i][3], blobs[i][4]
+    for dx = -r, r do
+        for dy = -r, r do
+            for dz = -r, r do
+                local d = math.sqrt(dx * dx + dy * dy + dz * dz)
+                local n = Perlin((x + dx) * 0.2, (y + dy) * 0.2, (z + dz) * 0.2)
+                if d <= r + n and tree:IsEmpty(x + dx, y + dy, z + dz) then
+                    tree:Set(x + dx, y + dy, z + dz, leaves[math.random(#leaves)])
+                end
+            end
+        end
+    end
+end
+
+return tree
@@ -0,0 +1,30 @@
+-- prompt: a windmill with spinning blades
+-- tags: building, animation, parts
+local size = 21
+
+local tower = Schematic(size, 30, size)
+tower:Cone(10, 0, 10, 6, 22, "d2b48c")
+tower:Pyramid(10, 21, 10, 3, 4, "8b0000")
+tower:Fill(10, 0, 4, 10, 2, 4, "5c3a1e")
+
+-- Every frame of the blades is built in the same space as the tower so they line up
+local function blades(diagonal)
+    local b = Schematic(size, 30, size)
+    b:Set(10, 18, 2, "4a4a4a")
+    for i = 1, 8 do
+        if diagonal then
+            b:Set(10 + i, 18 + i, 2, "f5f5dc")
+            b:Set(10 - i, 18 - i, 2, "f5f5dc")
+            b:Set(10 + i, 18 - i, 2, "f5f5dc")
+            b:Set(10 - i, 18 + i, 2, "f5f5dc")
+        else
+            b:Set(10 + i, 18, 2, "f5f5dc")
+            b:Set(10 - i, 18, 2, "f5f5dc")
+            b:Set(10, 18 + i, 2, "f5f5dc")
+            b:Set(10, 18 - i, 2, "f5f5dc")
+        end
+    end
+    return b
+end
+
+return { tower = tower, blades = Animation({ blades(false), blades(true) }, 4) }
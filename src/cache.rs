@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::nlp::Plan;
+
+/// Entries kept at most. When full, expired entries are dropped first and then the oldest ones.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Everything that decides what `/generate` returns for a new session
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    prompt: String,
+    model: &'static str,
+    prompt_version: u64,
+    /// `None` for requests without a seed, which accept any seed
+    seed: Option<u32>,
+    samples: usize,
+    plan: bool,
+}
+
+impl CacheKey {
+    pub fn new(
+        prompt: &str,
+        model: &'static str,
+        prompt_version: u64,
+        seed: Option<u32>,
+        samples: usize,
+        plan: bool,
+    ) -> Self {
+        CacheKey {
+            prompt: normalize(prompt),
+            model,
+            prompt_version,
+            seed,
+            samples,
+            plan,
+        }
+    }
+}
+
+/// Lowercase with runs of whitespace collapsed, so "A  house " and "a house" share an entry
+fn normalize(prompt: &str) -> String {
+    prompt
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A stored generation that can be returned again without calling the model or meshing
+#[derive(Clone, Debug)]
+pub struct CachedGeneration {
+    pub id: String,
+    pub url: String,
+    pub code: String,
+    pub seed: u32,
+    pub plan: Option<Plan>,
+}
+
+/// In-memory cache of generations. Like sessions, it is lost on restart.
+pub struct PromptCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, (Instant, CachedGeneration)>>,
+}
+
+impl PromptCache {
+    /// A `ttl` of zero disables the cache
+    pub fn new(ttl: Duration) -> Self {
+        PromptCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CachedGeneration> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((stored, generation)) if stored.elapsed() < self.ttl => Some(generation.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: CacheKey, generation: CachedGeneration) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+        }
+        while entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (stored, _))| *stored)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => entries.remove(&oldest),
+                None => break,
+            };
+        }
+        entries.insert(key, (Instant::now(), generation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CacheKey, CachedGeneration, PromptCache};
+
+    fn generation(id: &str) -> CachedGeneration {
+        CachedGeneration {
+            id: id.to_owned(),
+            url: format!("{}.glb", id),
+            code: String::new(),
+            seed: 7,
+            plan: None,
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = PromptCache::new(Duration::from_secs(60));
+        cache.insert(
+            CacheKey::new("A  house\n", "gpt-4", 1, None, 1, false),
+            generation("house"),
+        );
+
+        let hit = cache.get(&CacheKey::new("a house", "gpt-4", 1, None, 1, false));
+        assert_eq!(hit.unwrap().id, "house");
+        assert!(cache
+            .get(&CacheKey::new("a house", "gpt-4", 2, None, 1, false))
+            .is_none());
+        assert!(cache
+            .get(&CacheKey::new("a house", "gpt-4", 1, Some(7), 1, false))
+            .is_none());
+        assert!(cache
+            .get(&CacheKey::new("a big house", "gpt-4", 1, None, 1, false))
+            .is_none());
+    }
+
+    #[test]
+    fn test_ttl() {
+        let key = CacheKey::new("a tree", "gpt-4", 1, None, 1, false);
+
+        let disabled = PromptCache::new(Duration::ZERO);
+        disabled.insert(key.clone(), generation("tree"));
+        assert!(disabled.get(&key).is_none());
+
+        let cache = PromptCache::new(Duration::from_millis(10));
+        cache.insert(key.clone(), generation("tree"));
+        assert!(cache.get(&key).is_some());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get(&key).is_none());
+    }
+}
@@ -1,8 +1,72 @@
+mod css;
+
+pub use css::CSS_COLORS;
+
 /// 24-bit True color
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Color(pub u8, pub u8, pub u8);
 
+/// Which color string formats `Color::parse` accepts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseMode {
+    /// Only 6-digit hex without a leading #. Scripts are parsed leniently, so nothing outside the
+    /// tests selects this yet.
+    #[allow(dead_code)]
+    Strict,
+    /// Also accepts surrounding whitespace, a leading #, 3 and 8-digit hex, CSS color names and
+    /// `rgb(...)`/`rgba(...)` notation. Case is ignored and alpha channels are discarded.
+    Lenient,
+}
+
 impl Color {
+    /// Looks up a CSS named color, ignoring case
+    pub fn from_name(name: &str) -> Option<Color> {
+        let name = name.to_ascii_lowercase();
+        CSS_COLORS
+            .binary_search_by_key(&name.as_str(), |(n, _)| n)
+            .ok()
+            .map(|i| CSS_COLORS[i].1)
+    }
+
+    /// `hue` is in degrees and wraps around. `saturation` and `value` are clamped to [0, 1].
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
+        let h = hue.rem_euclid(360.) / 60.;
+        let s = saturation.clamp(0., 1.);
+        let v = value.clamp(0., 1.);
+
+        let chroma = v * s;
+        let x = chroma * (1. - (h % 2. - 1.).abs());
+        let (r, g, b) = match h as u8 {
+            0 => (chroma, x, 0.),
+            1 => (x, chroma, 0.),
+            2 => (0., chroma, x),
+            3 => (0., x, chroma),
+            4 => (x, 0., chroma),
+            _ => (chroma, 0., x),
+        };
+
+        let m = v - chroma;
+        Color::from_rgb_normalized([r + m, g + m, b + m])
+    }
+
+    /// Channels are clamped to [0, 1]
+    pub fn from_rgb_normalized(rgb: [f32; 3]) -> Color {
+        let [r, g, b] = rgb.map(|c| (c.clamp(0., 1.) * u8::MAX as f32).round() as u8);
+        Color(r, g, b)
+    }
+
+    /// Linearly interpolates towards `other`. `t` is clamped to [0, 1].
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0., 1.);
+        let (a, b) = (self.to_rgb_normalized(), other.to_rgb_normalized());
+        Color::from_rgb_normalized([0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t))
+    }
+
+    /// Multiplies every channel by `factor`. Values below 1 darken and values above 1 lighten.
+    pub fn shade(self, factor: f32) -> Color {
+        Color::from_rgb_normalized(self.to_rgb_normalized().map(|c| c * factor.max(0.)))
+    }
+
     pub fn to_rgb_normalized(self) -> [f32; 3] {
         [
             self.0 as f32 / u8::MAX as f32,
@@ -11,8 +75,25 @@ impl Color {
         ]
     }
 
+    /// 6-digit lowercase hex string without a leading #
+    pub fn to_hex_string(self) -> String {
+        format!("{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    /// Parses a color in the formats `mode` accepts
+    pub fn parse(s: &str, mode: ParseMode) -> Result<Color, InvalidColorHex> {
+        match mode {
+            ParseMode::Strict => Color::try_from_hex_string(s),
+            ParseMode::Lenient => {
+                parse_lenient(s.trim()).ok_or_else(|| InvalidColorHex(s.to_owned()))
+            }
+        }
+    }
+
+    /// Parses 6-digit hex without a leading #, the same as `ParseMode::Strict`
     pub fn try_from_hex_string(s: &str) -> Result<Color, InvalidColorHex> {
-        if s.len() != 6 {
+        // Slicing below needs every digit to be a single byte
+        if s.len() != 6 || !s.is_ascii() {
             return Err(InvalidColorHex(s.to_owned()));
         }
 
@@ -32,11 +113,64 @@ impl Color {
 #[derive(Debug, PartialEq)]
 pub struct InvalidColorHex(String);
 
+fn parse_lenient(s: &str) -> Option<Color> {
+    if let Some(color) = Color::from_name(s) {
+        return Some(color);
+    }
+
+    let lower = s.to_ascii_lowercase();
+    if let Some(args) = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return parse_rgb_function(args);
+    }
+
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+    let pair = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    match hex.len() {
+        // Each digit is repeated, so "f80" is "ff8800"
+        3 => Some(Color(digit(0) * 17, digit(1) * 17, digit(2) * 17)),
+        // The last pair is alpha
+        6 | 8 => Some(Color(pair(0), pair(1), pair(2))),
+        _ => None,
+    }
+}
+
+/// Parses the arguments of `rgb(...)` in either the comma-separated `rgb(255, 128, 0)` or the
+/// space-separated `rgb(255 128 0 / 50%)` form. Channels may be numbers from 0 to 255 or
+/// percentages and are clamped.
+fn parse_rgb_function(args: &str) -> Option<Color> {
+    let parts: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return None;
+    }
+
+    let mut channels = [0.; 3];
+    for (channel, part) in channels.iter_mut().zip(&parts) {
+        *channel = match part.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.,
+            None => part.parse::<f32>().ok()? / u8::MAX as f32,
+        };
+    }
+
+    Some(Color::from_rgb_normalized(channels))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::color::InvalidColorHex;
 
-    use super::Color;
+    use super::{Color, ParseMode, CSS_COLORS};
 
     #[test]
     fn test_size() {
@@ -57,6 +191,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_to_hex_string() {
+        assert_eq!(Color(0, 0, 0).to_hex_string(), "000000");
+        assert_eq!(Color(0x12, 0x3A, 0xBC).to_hex_string(), "123abc");
+        assert_eq!(
+            Color::try_from_hex_string(&Color(1, 254, 128).to_hex_string()),
+            Ok(Color(1, 254, 128))
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let colors = [
+            ("8b4513", Color(0x8B, 0x45, 0x13)),
+            ("#8B4513", Color(0x8B, 0x45, 0x13)),
+            ("  8b4513 ", Color(0x8B, 0x45, 0x13)),
+            ("\t123abc\n", Color(0x12, 0x3A, 0xBC)),
+            (" 123 ", Color(0x11, 0x22, 0x33)),
+            ("8b451380", Color(0x8B, 0x45, 0x13)),
+            ("#8b4513ff", Color(0x8B, 0x45, 0x13)),
+            ("f80", Color(0xFF, 0x88, 0x00)),
+            ("#F80", Color(0xFF, 0x88, 0x00)),
+            ("brown", Color(0xA5, 0x2A, 0x2A)),
+            (" Brown", Color(0xA5, 0x2A, 0x2A)),
+            ("rgb(139, 69, 19)", Color(139, 69, 19)),
+            ("RGB(139,69,19)", Color(139, 69, 19)),
+            ("rgba(139, 69, 19, 0.5)", Color(139, 69, 19)),
+            ("rgb(139 69 19 / 50%)", Color(139, 69, 19)),
+            ("rgb(100%, 50%, 0%)", Color(255, 128, 0)),
+            ("rgb(300, -5, 0)", Color(255, 0, 0)),
+        ];
+        for (s, color) in colors {
+            assert_eq!(Color::parse(s, ParseMode::Lenient), Ok(color), "{}", s);
+        }
+
+        let errors = [
+            "",
+            "#",
+            "8b45",
+            "8b45131",
+            "bcdefg",
+            "+1+2+3",
+            "bark",
+            "rgb(1, 2)",
+            "rgb(a, b, c)",
+            "rgb(1, 2, 3",
+            "77\u{00A7}777",
+            " 123ab",
+            "a\u{00A7}bcd",
+        ];
+        for err in errors {
+            assert_eq!(
+                Color::parse(err, ParseMode::Lenient),
+                Err(InvalidColorHex(err.to_owned()))
+            );
+        }
+
+        assert_eq!(
+            Color::parse("8b4513", ParseMode::Strict),
+            Ok(Color(0x8B, 0x45, 0x13))
+        );
+        let errors = [
+            "#8b4513",
+            " 8b4513",
+            " 123ab",
+            "cde56 ",
+            "brown",
+            "f80",
+            "rgb(1, 2, 3)",
+            "a\u{00A7}bcd",
+        ];
+        for s in errors {
+            assert_eq!(
+                Color::parse(s, ParseMode::Strict),
+                Err(InvalidColorHex(s.to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Color::from_name("brown"), Some(Color(0xA5, 0x2A, 0x2A)));
+        assert_eq!(
+            Color::from_name("SaddleBrown"),
+            Some(Color(0x8B, 0x45, 0x13))
+        );
+        assert_eq!(Color::from_name("aliceblue"), Some(Color(0xF0, 0xF8, 0xFF)));
+        assert_eq!(
+            Color::from_name("yellowgreen"),
+            Some(Color(0x9A, 0xCD, 0x32))
+        );
+        assert_eq!(Color::from_name("bark"), None);
+
+        assert!(CSS_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_from_hsv() {
+        assert_eq!(Color::from_hsv(0., 1., 1.), Color(255, 0, 0));
+        assert_eq!(Color::from_hsv(120., 1., 1.), Color(0, 255, 0));
+        assert_eq!(Color::from_hsv(240., 1., 1.), Color(0, 0, 255));
+        assert_eq!(Color::from_hsv(-120., 1., 1.), Color(0, 0, 255));
+        assert_eq!(Color::from_hsv(60., 1., 0.5), Color(128, 128, 0));
+        assert_eq!(Color::from_hsv(200., 0., 1.), Color(255, 255, 255));
+        assert_eq!(Color::from_hsv(0., 0., 0.), Color(0, 0, 0));
+    }
+
+    #[test]
+    fn test_lerp_shade() {
+        let (black, white) = (Color(0, 0, 0), Color(255, 255, 255));
+        assert_eq!(black.lerp(white, 0.), black);
+        assert_eq!(black.lerp(white, 1.), white);
+        assert_eq!(black.lerp(white, 0.5), Color(128, 128, 128));
+        assert_eq!(black.lerp(white, 2.), white);
+
+        assert_eq!(Color(200, 100, 50).shade(0.5), Color(100, 50, 25));
+        assert_eq!(Color(200, 100, 50).shade(2.), Color(255, 200, 100));
+        assert_eq!(Color(200, 100, 50).shade(-1.), black);
+    }
+
     #[test]
     fn test_from_octal_str() {
         assert_eq!(Color::try_from_hex_string("000000"), Ok(Color(0, 0, 0)));
@@ -1,9 +1,18 @@
+mod cache;
 mod color;
 mod nlp;
+mod noise;
+mod palette;
 mod schematic;
+mod search;
 mod server;
 mod storage;
+mod usage;
 
+use std::path::PathBuf;
+
+use nlp::{ExampleLibrary, Prompts, Selector};
+use search::{Embedder, HashingEmbedder, OpenAiEmbedder, SearchIndex};
 use storage::CloudflareR2Storage;
 use storage::FileSystemStorage;
 use storage::ObjectStorage;
@@ -28,6 +37,10 @@ async fn main() {
     };
 
     let openai_key = expect_env("OPENAI_API_KEY");
+    let admin_token = std::env::var("ADMIN_TOKEN").ok();
+    if admin_token.is_none() {
+        tracing::warn!("ADMIN_TOKEN is not set, admin routes are disabled");
+    }
 
     let storage: Box<dyn ObjectStorage> = if std::env::var("FILE_SYSTEM_STORAGE").is_ok() {
         tracing::info!("Generations will be stored on the file system");
@@ -35,12 +48,14 @@ async fn main() {
     } else {
         tracing::info!("Using Cloudflare R2 for object storage");
         let bucket_name = expect_env("R2_BUCKET_NAME");
+        let records_bucket_name = expect_env("R2_RECORDS_BUCKET_NAME");
         let account_id = expect_env("R2_ACCOUNT_ID");
         let public_url = expect_env("R2_PUBLIC_URL");
 
         Box::new(
             CloudflareR2Storage::new(
                 &bucket_name,
+                &records_bucket_name,
                 account_id,
                 s3::creds::Credentials::default().unwrap(), // loads from ENV
                 public_url,
@@ -49,7 +64,56 @@ async fn main() {
         )
     };
 
-    server::run(config, openai_key, storage).await;
+    let prices = match std::env::var("PRICE_TABLE") {
+        Ok(json) => usage::PriceTable::from_json(&json).expect("PRICE_TABLE is invalid"),
+        Err(_) => usage::PriceTable::default(),
+    };
+
+    // Seconds a generation stays cached, 0 disables the cache
+    let cache_ttl = std::time::Duration::from_secs(parse_env("CACHE_TTL", 24 * 60 * 60));
+    // Seconds a design session is kept after it was last used
+    let session_ttl = std::time::Duration::from_secs(parse_env("SESSION_TTL", 24 * 60 * 60));
+
+    let embedder: Box<dyn Embedder> = if std::env::var("LOCAL_EMBEDDINGS").is_ok() {
+        tracing::info!("Prompts will be embedded locally");
+        Box::new(HashingEmbedder)
+    } else {
+        Box::new(OpenAiEmbedder::new(openai_key.clone()))
+    };
+    let prompts_dir = parse_env("PROMPTS_DIR", PathBuf::from("prompts"));
+    let prompts = Prompts::load(&prompts_dir, &parse_env("PROMPT_VERSION", "v1".to_owned()))
+        .expect("invalid prompt templates");
+
+    let selector = parse_env("EXAMPLE_SELECTOR", Selector::Tags);
+    let examples_dir = parse_env("EXAMPLES_DIR", PathBuf::from("examples"));
+    let example_count = parse_env("EXAMPLE_COUNT", 3);
+    let mut examples =
+        ExampleLibrary::load(&examples_dir, selector, example_count).expect("invalid example");
+    if selector == Selector::Similar {
+        if let Err(e) = examples.embed(embedder.as_ref()).await {
+            tracing::warn!("failed to embed examples, selecting them by tags: {}", e);
+            examples = ExampleLibrary::load(&examples_dir, Selector::Tags, example_count)
+                .expect("invalid example");
+        }
+    }
+
+    let search = SearchIndex::load(embedder, storage.as_ref())
+        .await
+        .expect("failed to load search index");
+
+    server::run(
+        config,
+        openai_key,
+        admin_token,
+        storage,
+        prices,
+        cache_ttl,
+        session_ttl,
+        search,
+        examples,
+        prompts,
+    )
+    .await;
 }
 
 fn parse_env<T: std::str::FromStr>(var: &str, default: T) -> T {
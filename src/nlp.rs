@@ -1,98 +1,467 @@
 use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
 
 use rlua::Error::RuntimeError;
-use rlua::{Lua, StdLib};
-use serde::Deserialize;
+use rlua::{FromLua, Lua, StdLib};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::{color::Color, schematic::Schematic};
+use crate::color::{Color, ParseMode};
+use crate::schematic::{Axis, Material, Model, Schematic, Voxel};
 
-const SYSTEM_MESSAGE: &str = r#"You are a program that generates voxel art based on a prompt. You \
-generate Lua code which is executed in a sandbox to construct the mesh. \
-The Lua API is as follows:
+mod bindings;
+mod examples;
+mod extract;
+mod plan;
+mod prompts;
+mod score;
+mod session;
 
--- Creates a schematic
--- Max size along any axis is 128
-function Schematic(xSize: number, ySize: number, zSize: number): Schematic
+use bindings::{GlobalRegistrar, MethodRegistrar};
+pub use examples::{Example, ExampleLibrary, Selector};
+pub use plan::Plan;
+pub use prompts::Prompts;
+pub use score::Score;
+pub use session::{Session, SessionStore};
 
--- Bounds are (0, the size of the axis - 1)
--- Color is a 6-digit hex string without the #.
-function Schematic:Set(x: number, y: number, z: number, color: string)
+/// Model used for every request
+pub const MODEL: &str = "gpt-4";
 
--- Bounds are (0, the size of the axis - 1)
-function Schematic:Fill(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number, color: string)
+/// Maximum length of a response in tokens
+const MAX_TOKENS: usize = 512;
 
-DO NOT GENERATE AN EXPLANATION- ONLY CODE. Your response will not be shown to the user, only the \
-result of the code you produce will be apparent. The code *must* end with a return statement that \
-designates which schematic to be generated."#;
+/// Responses cut off at `MAX_TOKENS` are retried with twice the limit until it reaches this
+const MAX_RETRY_TOKENS: usize = 2048;
+
+/// Most completions that can be requested at once
+pub const MAX_SAMPLES: usize = 8;
+
+/// Sampling several completions at temperature 0 would return the same code every time
+const SAMPLING_TEMPERATURE: f32 = 0.8;
 
 #[derive(Debug, Deserialize)]
 struct Response {
     choices: Vec<ResponseMessage>,
+    #[serde(default)]
+    usage: Usage,
+}
+
+/// Tokens used by one or more requests
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct ResponseMessage {
-    message: Message,
+    message: Reply,
     finish_reason: String,
 }
 
 #[derive(Debug, Deserialize)]
+struct Reply {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCall {
+    function: FunctionCall,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionCall {
+    name: String,
+    /// JSON encoded arguments
+    arguments: String,
+}
+
+/// Arguments of the submit_code function
+#[derive(Debug, Deserialize)]
+struct Submission {
+    #[serde(default)]
+    plan: String,
+    lua: String,
+}
+
+const SUBMIT_CODE: &str = "submit_code";
+
+impl Reply {
+    /// Arguments of the first call to the named function
+    fn arguments(&self, name: &str) -> Option<&str> {
+        self.tool_calls
+            .iter()
+            .find(|call| call.function.name == name)
+            .map(|call| call.function.arguments.as_str())
+    }
+
+    /// Prefers the arguments of a submit_code call and falls back to extracting code from the text
+    fn code(&self) -> String {
+        if let Some(arguments) = self.arguments(SUBMIT_CODE) {
+            match serde_json::from_str::<Submission>(arguments) {
+                Ok(submission) => {
+                    tracing::info!(plan = submission.plan);
+                    return extract::extract_code(&submission.lua);
+                }
+                Err(e) => tracing::warn!("invalid {} arguments: {}", SUBMIT_CODE, e),
+            }
+        }
+
+        extract::extract_code(self.content.as_deref().unwrap_or_default())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 struct Message {
     role: String,
     content: String,
 }
 
-pub async fn build(api_key: &str, prompt: &str) -> Result<Schematic, NlpError> {
-    let code = generate_code(api_key, prompt).await?;
+impl Message {
+    fn user(content: &str) -> Self {
+        Message {
+            role: "user".to_owned(),
+            content: content.to_owned(),
+        }
+    }
+
+    fn assistant(content: &str) -> Self {
+        Message {
+            role: "assistant".to_owned(),
+            content: content.to_owned(),
+        }
+    }
+}
+
+/// A built model along with the code that produced it
+pub struct Generation {
+    /// Only set if a planning stage was requested
+    pub plan: Option<Plan>,
+    pub code: String,
+    pub model: Model,
+    /// Scores of every sample in the order they were received, including the chosen one
+    pub scores: Vec<Score>,
+}
+
+/// Builds a model for `prompt` with the earlier turns of `session` as context. If `samples` is
+/// above 1, that many completions are generated and the best scoring one is kept. If `plan` is
+/// set, a plan is made first and the code is written from it, which leaves the whole token budget
+/// of the second request for code. `examples` are shown as earlier turns before the session. The
+/// prompt, the plan, the chosen code and any Lua error are added to the session. Tokens used by
+/// every request are added to `usage`, even if the build fails.
+#[allow(clippy::too_many_arguments)]
+pub async fn build(
+    api_key: &str,
+    prompts: &Prompts,
+    session: &mut Session,
+    prompt: &str,
+    seed: u32,
+    samples: usize,
+    plan: bool,
+    examples: &[&Example],
+    usage: &mut Usage,
+) -> Result<Generation, NlpError> {
+    let mut turn = session.clone();
+    let plan = if plan {
+        Some(make_plan(api_key, prompts, session, prompt, usage).await?)
+    } else {
+        None
+    };
 
+    match &plan {
+        Some(plan) => turn.push(Message::user(
+            &prompts.follow_plan(prompt, &serde_json::to_string(plan).unwrap()),
+        )),
+        None => turn.push(Message::user(prompt)),
+    }
+    let mut messages = examples::messages(examples);
+    let budget = session::history_budget(prompts.system(), MAX_RETRY_TOKENS).saturating_sub(
+        messages
+            .iter()
+            .map(|m| session::estimate_tokens(&m.content))
+            .sum(),
+    );
+    messages.extend_from_slice(turn.recent(budget));
+    let codes = generate_code(api_key, prompts, &messages, samples, usage).await?;
+
+    let (code, result, scores) = run_samples(codes, seed).await;
+    turn.push(Message::assistant(&code));
+    if let Err(e) = &result {
+        turn.push_error(&e.to_string());
+    }
+    *session = turn;
+
+    let model = result.map_err(|e| NlpError::Lua(e, scores.clone()))?;
+    Ok(Generation {
+        plan,
+        code,
+        model,
+        scores,
+    })
+}
+
+/// Asks for a plan for `prompt` with the earlier turns of `session` as context
+async fn make_plan(
+    api_key: &str,
+    prompts: &Prompts,
+    session: &Session,
+    prompt: &str,
+    usage: &mut Usage,
+) -> Result<Plan, NlpError> {
+    let mut turn = session.clone();
+    turn.push(Message::user(prompt));
+    let budget = session::history_budget(prompts.plan_system(), MAX_RETRY_TOKENS);
+    let tool = Plan::tool();
+    let replies = request(
+        api_key,
+        prompts.plan_system(),
+        turn.recent(budget),
+        1,
+        &tool,
+        usage,
+    )
+    .await?;
+
+    let arguments = replies[0].arguments(tool.name).unwrap_or_default();
+    let plan: Plan = serde_json::from_str(arguments)
+        .map_err(|e| NlpError::Deserialize(e, arguments.to_owned()))?;
+    plan.validate().map_err(NlpError::InvalidPlan)?;
+    tracing::info!(plan = ?plan);
+    Ok(plan)
+}
+
+/// Changes the code previously generated for `prompt` as described by `instruction`, such as
+/// "make the roof red". Tokens used are added to `usage`, even if the edit fails.
+#[allow(clippy::too_many_arguments)]
+pub async fn edit(
+    api_key: &str,
+    prompts: &Prompts,
+    prompt: &str,
+    code: &str,
+    instruction: &str,
+    seed: u32,
+    samples: usize,
+    usage: &mut Usage,
+) -> Result<Generation, NlpError> {
+    let messages = [
+        Message::user(prompt),
+        Message::assistant(code),
+        Message::user(&prompts.edit(instruction)),
+    ];
+    let codes = generate_code(api_key, prompts, &messages, samples, usage).await?;
+    let (code, result, scores) = run_samples(codes, seed).await;
+    let model = result.map_err(|e| NlpError::Lua(e, scores.clone()))?;
+    Ok(Generation {
+        plan: None,
+        code,
+        model,
+        scores,
+    })
+}
+
+/// Runs `pick_best` on the blocking thread pool, as scripts can take long enough to stall every
+/// other request on the runtime
+async fn run_samples(codes: Vec<String>, seed: u32) -> (String, rlua::Result<Model>, Vec<Score>) {
+    tokio::task::spawn_blocking(move || pick_best(codes, seed))
+        .await
+        .expect("executing samples panicked")
+}
+
+/// Executes every sample and returns the code and result of the best one along with the scores
+/// of all samples. Ties go to the earliest sample. `codes` must not be empty.
+fn pick_best(codes: Vec<String>, seed: u32) -> (String, rlua::Result<Model>, Vec<Score>) {
+    let mut samples: Vec<_> = codes
+        .into_iter()
+        .map(|code| {
+            let result = execute(&code, seed);
+            (code, result)
+        })
+        .collect();
+    let scores: Vec<_> = samples
+        .iter()
+        .map(|(_, result)| Score::of(result))
+        .collect();
+
+    let best = (0..scores.len()).fold(0, |best, i| {
+        if scores[i].total > scores[best].total {
+            i
+        } else {
+            best
+        }
+    });
+    let (code, result) = samples.swap_remove(best);
+    (code, result, scores)
+}
+
+/// Runs generated code in a sandbox. All randomness available to the code is derived from `seed`,
+/// so the same code and seed always produce the same model.
+fn execute(code: &str, seed: u32) -> rlua::Result<Model> {
     let lua = Lua::new_with(StdLib::MATH);
     lua.context(|ctx| {
-        let schematic_ctor = ctx.create_function(|_, (x_size, y_size, z_size): (u8, u8, u8)| {
-            if x_size > 128 || y_size > 128 || z_size > 128 {
-                return Err(RuntimeError(format!(
-                    "schematic size {}x{}x{} too big",
-                    x_size, y_size, z_size
-                )));
-            }
-            Ok(Schematic::new(x_size, y_size, z_size))
-        })?;
-        ctx.globals().set("Schematic", schematic_ctor)?;
+        bindings::globals(&mut GlobalRegistrar(ctx), seed)?;
 
-        Ok(ctx.load(&code).eval()?)
+        let math: rlua::Table = ctx.globals().get("math")?;
+        let randomseed: rlua::Function = math.get("randomseed")?;
+        randomseed.call::<_, ()>(seed)?;
+
+        let LuaModel(model) = ctx.load(code).eval()?;
+        Ok(model)
     })
-    .map_err(|e| NlpError::Lua(e))
 }
 
-async fn generate_code(api_key: &str, prompt: &str) -> Result<String, NlpError> {
-    let response_str = invoke_openai(api_key, prompt)
-        .await
-        .map_err(|e| NlpError::Network(e))?;
-    tracing::info!(response = response_str);
+/// Returns the code of every completion. There is always at least one.
+async fn generate_code(
+    api_key: &str,
+    prompts: &Prompts,
+    messages: &[Message],
+    samples: usize,
+    usage: &mut Usage,
+) -> Result<Vec<String>, NlpError> {
+    let replies = request(
+        api_key,
+        prompts.system(),
+        messages,
+        samples,
+        &Tool::submit_code(),
+        usage,
+    )
+    .await?;
 
-    let response_json: Response =
-        serde_json::from_str(&response_str).map_err(|e| NlpError::Deserialize(e, response_str))?;
+    Ok(replies.iter().map(Reply::code).collect())
+}
+
+/// A function the model is made to call with its answer
+struct Tool {
+    name: &'static str,
+    description: &'static str,
+    /// JSON schema of the arguments
+    parameters: serde_json::Value,
+}
 
-    let message = &response_json.choices[0].message.content;
+impl Tool {
+    fn submit_code() -> Tool {
+        Tool {
+            name: SUBMIT_CODE,
+            description: "Submits the Lua code that builds the voxel art",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "plan": {
+                        "type": "string",
+                        "description": "A few short sentences on the shapes, sizes and colors to build",
+                    },
+                    "lua": {
+                        "type": "string",
+                        "description": "The complete Lua code, without Markdown fences",
+                    },
+                },
+                "required": ["plan", "lua"],
+            }),
+        }
+    }
+}
 
-    Ok(match message.strip_prefix("```lua") {
-        Some(str) => str[..str.len() - 3].to_owned(),
-        None => message.to_owned(),
+/// Sends `messages` after `system_message` and asks for `samples` completions that call `tool`.
+/// Replies that were cut off by the token limit are discarded. If every reply was cut off, the
+/// request is retried with a higher limit. Tokens used by every attempt are added to `usage`.
+/// There is always at least one reply.
+async fn request(
+    api_key: &str,
+    system_message: &str,
+    messages: &[Message],
+    samples: usize,
+    tool: &Tool,
+    usage: &mut Usage,
+) -> Result<Vec<Reply>, NlpError> {
+    retry_truncated(usage, |max_tokens| {
+        invoke_openai(api_key, system_message, messages, samples, tool, max_tokens)
     })
+    .await
+}
+
+/// Calls `send` with a token limit and parses the response, doubling the limit while every reply
+/// is cut off
+async fn retry_truncated<F, R>(usage: &mut Usage, mut send: F) -> Result<Vec<Reply>, NlpError>
+where
+    F: FnMut(usize) -> R,
+    R: Future<Output = Result<String, reqwest::Error>>,
+{
+    let mut max_tokens = MAX_TOKENS;
+    loop {
+        let response_str = send(max_tokens).await.map_err(NlpError::Network)?;
+        tracing::info!(response = response_str);
+
+        let response_json: Response = serde_json::from_str(&response_str)
+            .map_err(|e| NlpError::Deserialize(e, response_str))?;
+        usage.add(response_json.usage);
+
+        if response_json.choices.is_empty() {
+            return Err(NlpError::NoChoices);
+        }
+
+        let replies: Vec<_> = response_json
+            .choices
+            .into_iter()
+            .filter(|choice| choice.finish_reason != "length")
+            .map(|choice| choice.message)
+            .collect();
+        if !replies.is_empty() {
+            return Ok(replies);
+        }
+
+        if max_tokens >= MAX_RETRY_TOKENS {
+            return Err(NlpError::Truncated(max_tokens));
+        }
+        tracing::warn!("every reply was cut off at {} tokens, retrying", max_tokens);
+        max_tokens *= 2;
+    }
 }
 
-async fn invoke_openai(api_key: &str, prompt: &str) -> Result<String, reqwest::Error> {
+async fn invoke_openai(
+    api_key: &str,
+    system_message: &str,
+    messages: &[Message],
+    samples: usize,
+    tool: &Tool,
+    max_tokens: usize,
+) -> Result<String, reqwest::Error> {
+    let messages: Vec<_> = std::iter::once(Message {
+        role: "system".to_owned(),
+        content: system_message.to_owned(),
+    })
+    .chain(messages.iter().cloned())
+    .collect();
+
     let client = reqwest::Client::new();
     client
         .post("https://api.openai.com/v1/chat/completions")
         .json(&json!({
-            "model": "gpt-4",
-            "messages": [
-                { "role": "system", "content": SYSTEM_MESSAGE },
-                { "role": "user", "content": prompt },
-            ],
-            "max_tokens": 512,
-            "temperature": 0.0,
+            "model": MODEL,
+            "messages": messages,
+            "max_tokens": max_tokens,
+            "n": samples,
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                },
+            }],
+            "tool_choice": { "type": "function", "function": { "name": tool.name } },
+            "temperature": if samples > 1 { SAMPLING_TEMPERATURE } else { 0.0 },
         }))
         .header("Authorization", format!("Bearer {}", api_key))
         .send()
@@ -104,7 +473,12 @@ async fn invoke_openai(api_key: &str, prompt: &str) -> Result<String, reqwest::E
 pub enum NlpError {
     Network(reqwest::Error),
     Deserialize(serde_json::Error, String),
-    Lua(rlua::Error),
+    /// The best sample failed, which means every sample did. Holds the scores of all samples.
+    Lua(rlua::Error, Vec<Score>),
+    NoChoices,
+    InvalidPlan(String),
+    /// Every reply was still cut off at this many tokens
+    Truncated(usize),
 }
 
 impl fmt::Display for NlpError {
@@ -114,57 +488,291 @@ impl fmt::Display for NlpError {
             Self::Deserialize(e, src) => {
                 write!(f, "deserialization failed: {}, original: {}", e, src)
             }
-            Self::Lua(e) => write!(f, "error executing Lua: {}", e),
+            Self::Lua(e, _) => write!(f, "error executing Lua: {}", e),
+            Self::NoChoices => write!(f, "response contained no choices"),
+            Self::InvalidPlan(e) => write!(f, "invalid plan: {}", e),
+            Self::Truncated(tokens) => write!(f, "response was cut off at {} tokens", tokens),
         }
     }
 }
 
 impl rlua::UserData for Schematic {
     fn add_methods<'lua, T: rlua::UserDataMethods<'lua, Self>>(methods: &mut T) {
-        methods.add_method_mut(
-            "Set",
-            |_, schematic, (x, y, z, color_str): (_, _, _, String)| {
-                let color = match Color::try_from_hex_string(&color_str) {
-                    Ok(c) => c,
-                    Err(_) => {
-                        return Err(RuntimeError(format!("color \"{}\" is invalid", color_str)))
-                    }
-                };
-
-                match schematic.set(x, y, z, color) {
-                    Some(_) => Ok(()),
-                    None => Err(RuntimeError(format!(
-                        "{}, {}, {} is out of bounds",
-                        x, y, z
-                    ))),
-                }
-            },
-        );
-
-        methods.add_method_mut(
-            "Fill",
-            |_, schematic, (x1, y1, z1, x2, y2, z2, color_str): (_, _, _, _, _, _, String)| {
-                let color = match Color::try_from_hex_string(&color_str) {
-                    Ok(c) => c,
-                    Err(_) => {
-                        return Err(RuntimeError(format!("color \"{}\" is invalid", color_str)))
-                    }
-                };
-
-                match schematic.fill(x1, y1, z1, x2, y2, z2, color) {
-                    Some(_) => Ok(()),
-                    None => Err(RuntimeError(format!(
-                        "fill from {}, {}, {} to {}, {}, {} overlaps an out-of-bounds area",
-                        x1, y1, z1, x2, y2, z2,
-                    ))),
+        bindings::schematic_methods(&mut MethodRegistrar(methods));
+    }
+}
+
+impl rlua::UserData for Color {}
+
+/// Color argument that accepts either a hex string or a color created by one of the color helpers
+struct LuaColor(Color);
+
+impl<'lua> FromLua<'lua> for LuaColor {
+    fn from_lua(value: rlua::Value<'lua>, ctx: rlua::Context<'lua>) -> rlua::Result<Self> {
+        match value {
+            rlua::Value::UserData(data) => Ok(LuaColor(*data.borrow::<Color>()?)),
+            value => parse_color(&String::from_lua(value, ctx)?).map(LuaColor),
+        }
+    }
+}
+
+const MAX_FRAMES: usize = 64;
+
+/// Frames created by the `Animation` function
+#[derive(Clone)]
+struct LuaAnimation(Model);
+
+impl rlua::UserData for LuaAnimation {}
+
+/// Deepest nesting of parts that a script may return
+const MAX_PART_DEPTH: usize = 16;
+
+/// Return value of a script: either a schematic, an animation or a table of named parts, which may
+/// be nested. Parts in an array are named by their position.
+struct LuaModel(Model);
+
+impl<'lua> FromLua<'lua> for LuaModel {
+    fn from_lua(value: rlua::Value<'lua>, ctx: rlua::Context<'lua>) -> rlua::Result<Self> {
+        // Tables are compared by identity when used as keys, which makes this a set of tables
+        let visited = ctx.create_table()?;
+        LuaModel::read(value, ctx, &visited, 0)
+    }
+}
+
+impl LuaModel {
+    /// Every table is only read once, which rejects tables that contain themselves
+    fn read<'lua>(
+        value: rlua::Value<'lua>,
+        ctx: rlua::Context<'lua>,
+        visited: &rlua::Table<'lua>,
+        depth: usize,
+    ) -> rlua::Result<Self> {
+        let table = match value {
+            rlua::Value::Table(table) => table,
+            rlua::Value::UserData(data) if data.is::<LuaAnimation>() => {
+                return Ok(LuaModel(data.borrow::<LuaAnimation>()?.0.clone()))
+            }
+            value => return Ok(LuaModel(Model::Schematic(Schematic::from_lua(value, ctx)?))),
+        };
+
+        if depth >= MAX_PART_DEPTH {
+            return Err(RuntimeError(format!(
+                "parts must not be nested more than {} levels deep",
+                MAX_PART_DEPTH
+            )));
+        }
+        if visited.raw_get::<_, bool>(table.clone())? {
+            return Err(RuntimeError(
+                "a table of parts must not appear more than once".to_owned(),
+            ));
+        }
+        visited.raw_set(table.clone(), true)?;
+
+        let mut parts = Vec::new();
+        for pair in table.pairs::<rlua::Value, rlua::Value>() {
+            let (key, value) = pair?;
+            let (position, name) = match key {
+                rlua::Value::Integer(i) => (Some(i), i.to_string()),
+                rlua::Value::String(s) => (None, s.to_str()?.to_owned()),
+                _ => {
+                    return Err(RuntimeError(
+                        "part names must be strings or array positions".to_owned(),
+                    ))
                 }
-            },
-        );
+            };
+            let LuaModel(part) = LuaModel::read(value, ctx, visited, depth + 1)?;
+            parts.push((position, name, part));
+        }
 
-        methods.add_method("xSize", |_, schematic, ()| Ok(schematic.x_size()));
+        // Lua tables have no order, so array parts come first followed by named parts in
+        // alphabetical order
+        parts.sort_by(|a, b| (a.0.is_none(), a.0, &a.1).cmp(&(b.0.is_none(), b.0, &b.1)));
+        Ok(LuaModel(Model::Parts(
+            parts
+                .into_iter()
+                .map(|(_, name, part)| (name, part))
+                .collect(),
+        )))
+    }
+}
+
+/// Either the name of a material defined with `DefineMaterial` or a table of the form
+/// `{ color = ..., alpha = ..., emissive = ... }`
+enum LuaMaterial {
+    Named(String),
+    Inline(Voxel),
+}
 
-        methods.add_method("ySize", |_, schematic, ()| Ok(schematic.y_size()));
+impl LuaMaterial {
+    fn resolve(self, schematic: &Schematic) -> rlua::Result<Voxel> {
+        match self {
+            LuaMaterial::Named(name) => schematic
+                .material_voxel(&name)
+                .ok_or_else(|| RuntimeError(format!("material {} is not defined", name))),
+            LuaMaterial::Inline(voxel) => Ok(voxel),
+        }
+    }
+}
 
-        methods.add_method("zSize", |_, schematic, ()| Ok(schematic.z_size()));
+impl<'lua> FromLua<'lua> for LuaMaterial {
+    fn from_lua(value: rlua::Value<'lua>, ctx: rlua::Context<'lua>) -> rlua::Result<Self> {
+        if let rlua::Value::String(name) = value {
+            return Ok(LuaMaterial::Named(name.to_str()?.to_owned()));
+        }
+
+        let table = rlua::Table::from_lua(value, ctx)?;
+        let LuaColor(color) = table.get("color")?;
+        Ok(LuaMaterial::Inline(Voxel {
+            color,
+            alpha: get_factor(&table, "alpha", 1.)?,
+            emissive: get_emissive(&table)?,
+            material: None,
+        }))
+    }
+}
+
+/// Table argument of `DefineMaterial`
+struct LuaMaterialDefinition(Material);
+
+impl<'lua> FromLua<'lua> for LuaMaterialDefinition {
+    fn from_lua(value: rlua::Value<'lua>, ctx: rlua::Context<'lua>) -> rlua::Result<Self> {
+        let table = rlua::Table::from_lua(value, ctx)?;
+        let LuaColor(color) = table.get("color")?;
+        Ok(LuaMaterialDefinition(Material {
+            name: String::new(),
+            color,
+            alpha: get_factor(&table, "alpha", 1.)?,
+            emissive: get_emissive(&table)?,
+            roughness: get_factor(&table, "roughness", 1.)?,
+            metallic: get_factor(&table, "metallic", 0.)?,
+        }))
+    }
+}
+
+/// Reads an optional number between 0 and 1 from a table
+fn get_factor(table: &rlua::Table, key: &str, default: f32) -> rlua::Result<f32> {
+    let value: Option<f32> = table.get(key)?;
+    let value = value.unwrap_or(default);
+    if !(0. ..=1.).contains(&value) {
+        return Err(RuntimeError(format!(
+            "{} {} must be between 0 and 1",
+            key, value
+        )));
+    }
+    Ok(value)
+}
+
+fn get_emissive(table: &rlua::Table) -> rlua::Result<f32> {
+    let emissive: Option<f32> = table.get("emissive")?;
+    let emissive = emissive.unwrap_or(0.);
+    if !(emissive >= 0. && emissive.is_finite()) {
+        return Err(RuntimeError(format!(
+            "emissive {} must be 0 or more",
+            emissive
+        )));
+    }
+    Ok(emissive)
+}
+
+fn parse_color(color_str: &str) -> rlua::Result<Color> {
+    Color::parse(color_str, ParseMode::Lenient)
+        .map_err(|_| RuntimeError(format!("color \"{}\" is invalid", color_str)))
+}
+
+fn parse_axis(axis_str: &str) -> rlua::Result<Axis> {
+    Axis::from_str(axis_str).map_err(|_| RuntimeError(format!("axis \"{}\" is invalid", axis_str)))
+}
+
+fn shape_result(result: Option<()>, shape: &str) -> rlua::Result<()> {
+    result.ok_or_else(|| RuntimeError(format!("{} overlaps an out-of-bounds area", shape)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{execute, retry_truncated, NlpError, Usage, MAX_RETRY_TOKENS, MAX_TOKENS};
+
+    /// Response with one reply per finish reason, each using 10 tokens
+    fn response(finish_reasons: &[&str]) -> String {
+        let choices: Vec<_> = finish_reasons
+            .iter()
+            .map(|reason| {
+                json!({
+                    "message": { "content": "return Schematic(1, 1, 1)" },
+                    "finish_reason": reason,
+                })
+            })
+            .collect();
+        let tokens = 10 * finish_reasons.len();
+        json!({
+            "choices": choices,
+            "usage": { "prompt_tokens": 0, "completion_tokens": tokens, "total_tokens": tokens },
+        })
+        .to_string()
+    }
+
+    #[rocket::async_test]
+    async fn test_retry_truncated() {
+        // Cut off replies are dropped without a retry while another one finished
+        let mut limits = Vec::new();
+        let mut usage = Usage::default();
+        let replies = retry_truncated(&mut usage, |max_tokens| {
+            limits.push(max_tokens);
+            std::future::ready(Ok(response(&["length", "stop"])))
+        })
+        .await;
+        assert_eq!(replies.ok().map(|r| r.len()), Some(1));
+        assert_eq!(limits, [MAX_TOKENS]);
+
+        // The limit doubles until a reply fits
+        let mut limits = Vec::new();
+        let replies = retry_truncated(&mut usage, |max_tokens| {
+            limits.push(max_tokens);
+            let reason = if max_tokens < MAX_RETRY_TOKENS {
+                "length"
+            } else {
+                "stop"
+            };
+            std::future::ready(Ok(response(&[reason])))
+        })
+        .await;
+        assert_eq!(replies.ok().map(|r| r.len()), Some(1));
+        assert_eq!(limits, [MAX_TOKENS, MAX_TOKENS * 2, MAX_RETRY_TOKENS]);
+
+        // and gives up once it reaches `MAX_RETRY_TOKENS`
+        let mut limits = Vec::new();
+        let result = retry_truncated(&mut usage, |max_tokens| {
+            limits.push(max_tokens);
+            std::future::ready(Ok(response(&["length"])))
+        })
+        .await;
+        assert!(matches!(result, Err(NlpError::Truncated(MAX_RETRY_TOKENS))));
+        assert_eq!(limits, [MAX_TOKENS, MAX_TOKENS * 2, MAX_RETRY_TOKENS]);
+
+        // Tokens of every attempt are counted
+        assert_eq!(usage.total_tokens, 20 + 3 * 10 + 3 * 10);
+    }
+
+    #[test]
+    fn test_nested_parts() {
+        let code = "local s = Schematic(2, 2, 2) return { roof = s, walls = { s, s } }";
+        assert!(execute(code, 0).is_ok());
+
+        assert!(execute("local t = {} t.x = t return t", 0).is_err());
+        assert!(execute("local t = {} return { a = t, b = t }", 0).is_err());
+        let deep = "local t = Schematic(1, 1, 1) for i = 1, 100 do t = { t } end return t";
+        assert!(execute(deep, 0).is_err());
+    }
+
+    #[test]
+    fn test_noise_coordinates() {
+        let code = "Perlin(1e300, 0, 0) Simplex(0, -1e300, 0) Worley(0, 0, 1e300) \
+            return Schematic(1, 1, 1)";
+        assert!(execute(code, 0).is_ok());
+        for function in ["Perlin", "Simplex", "Worley"] {
+            let code = format!("{}(math.huge, 0, 0) return Schematic(1, 1, 1)", function);
+            assert!(execute(&code, 0).is_err(), "{}", function);
+        }
     }
 }
@@ -0,0 +1,1052 @@
+use std::str::FromStr;
+
+use rlua::Error::RuntimeError;
+use rlua::{Context, FromLuaMulti, ToLua, ToLuaMulti, UserDataMethods, Variadic};
+
+use crate::color::{Color, CSS_COLORS};
+use crate::noise::Noise;
+use crate::schematic::{CsgOp, Material, Model, PasteMode, Schematic};
+
+use super::{
+    parse_axis, shape_result, LuaAnimation, LuaColor, LuaMaterial, LuaMaterialDefinition, LuaModel,
+    MAX_FRAMES,
+};
+
+/// Documentation of a Lua binding, which is all the model knows about it
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Doc {
+    pub name: &'static str,
+    /// Parameters and return type for functions, like `(x: number): string?`, or the type of a
+    /// value
+    pub signature: &'static str,
+    /// Comment shown above the declaration. Lines are separated by `\n`.
+    pub docs: &'static str,
+}
+
+const fn doc(name: &'static str, signature: &'static str, docs: &'static str) -> Doc {
+    Doc {
+        name,
+        signature,
+        docs,
+    }
+}
+
+/// Receives the global functions and values available to scripts
+pub(super) trait GlobalRegistry<'lua> {
+    /// Adds a comment to the docs that applies to the following bindings
+    fn section(&mut self, text: &'static str);
+
+    fn function<A, R, F>(&mut self, doc: Doc, function: F) -> rlua::Result<()>
+    where
+        A: FromLuaMulti<'lua>,
+        R: ToLuaMulti<'lua>,
+        F: 'static + Send + Fn(Context<'lua>, A) -> rlua::Result<R>;
+
+    fn value<V, F>(&mut self, doc: Doc, value: F) -> rlua::Result<()>
+    where
+        V: ToLua<'lua>,
+        F: FnOnce(Context<'lua>) -> rlua::Result<V>;
+}
+
+/// Receives the methods of `Schematic`
+pub(super) trait MethodRegistry<'lua> {
+    /// Adds a comment to the docs that applies to the following bindings
+    fn section(&mut self, text: &'static str);
+
+    fn method<A, R, M>(&mut self, doc: Doc, method: M)
+    where
+        A: FromLuaMulti<'lua>,
+        R: ToLuaMulti<'lua>,
+        M: 'static + Send + Fn(Context<'lua>, &Schematic, A) -> rlua::Result<R>;
+
+    fn method_mut<A, R, M>(&mut self, doc: Doc, method: M)
+    where
+        A: FromLuaMulti<'lua>,
+        R: ToLuaMulti<'lua>,
+        M: 'static + Send + FnMut(Context<'lua>, &mut Schematic, A) -> rlua::Result<R>;
+}
+
+/// Sets every global binding in a Lua context
+pub(super) struct GlobalRegistrar<'lua>(pub Context<'lua>);
+
+impl<'lua> GlobalRegistry<'lua> for GlobalRegistrar<'lua> {
+    fn section(&mut self, _: &'static str) {}
+
+    fn function<A, R, F>(&mut self, doc: Doc, function: F) -> rlua::Result<()>
+    where
+        A: FromLuaMulti<'lua>,
+        R: ToLuaMulti<'lua>,
+        F: 'static + Send + Fn(Context<'lua>, A) -> rlua::Result<R>,
+    {
+        let function = self.0.create_function(function)?;
+        self.0.globals().set(doc.name, function)
+    }
+
+    fn value<V, F>(&mut self, doc: Doc, value: F) -> rlua::Result<()>
+    where
+        V: ToLua<'lua>,
+        F: FnOnce(Context<'lua>) -> rlua::Result<V>,
+    {
+        let value = value(self.0)?;
+        self.0.globals().set(doc.name, value)
+    }
+}
+
+/// Adds every method to the `Schematic` userdata
+pub(super) struct MethodRegistrar<'a, T>(pub &'a mut T);
+
+impl<'lua, T: UserDataMethods<'lua, Schematic>> MethodRegistry<'lua> for MethodRegistrar<'_, T> {
+    fn section(&mut self, _: &'static str) {}
+
+    fn method<A, R, M>(&mut self, doc: Doc, method: M)
+    where
+        A: FromLuaMulti<'lua>,
+        R: ToLuaMulti<'lua>,
+        M: 'static + Send + Fn(Context<'lua>, &Schematic, A) -> rlua::Result<R>,
+    {
+        self.0.add_method(doc.name, method);
+    }
+
+    fn method_mut<A, R, M>(&mut self, doc: Doc, method: M)
+    where
+        A: FromLuaMulti<'lua>,
+        R: ToLuaMulti<'lua>,
+        M: 'static + Send + FnMut(Context<'lua>, &mut Schematic, A) -> rlua::Result<R>,
+    {
+        self.0.add_method_mut(doc.name, method);
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum Entry {
+    Section(&'static str),
+    Function(Doc),
+    Method(Doc),
+    Value(Doc),
+}
+
+/// Collects the docs of every binding without registering anything
+#[derive(Default)]
+pub(super) struct DocCollector(pub Vec<Entry>);
+
+impl<'lua> GlobalRegistry<'lua> for DocCollector {
+    fn section(&mut self, text: &'static str) {
+        self.0.push(Entry::Section(text));
+    }
+
+    fn function<A, R, F>(&mut self, doc: Doc, _: F) -> rlua::Result<()> {
+        self.0.push(Entry::Function(doc));
+        Ok(())
+    }
+
+    fn value<V, F>(&mut self, doc: Doc, _: F) -> rlua::Result<()> {
+        self.0.push(Entry::Value(doc));
+        Ok(())
+    }
+}
+
+impl<'lua> MethodRegistry<'lua> for DocCollector {
+    fn section(&mut self, text: &'static str) {
+        self.0.push(Entry::Section(text));
+    }
+
+    fn method<A, R, M>(&mut self, doc: Doc, _: M) {
+        self.0.push(Entry::Method(doc));
+    }
+
+    fn method_mut<A, R, M>(&mut self, doc: Doc, _: M) {
+        self.0.push(Entry::Method(doc));
+    }
+}
+
+/// Every binding in the order they are documented. Methods follow the `Schematic` constructor.
+pub(super) fn entries() -> Vec<Entry> {
+    let mut globals_docs = DocCollector::default();
+    globals(&mut globals_docs, 0).unwrap();
+    let mut method_docs = DocCollector::default();
+    schematic_methods(&mut method_docs);
+
+    let mut entries = Vec::new();
+    for entry in globals_docs.0 {
+        let is_constructor = matches!(entry, Entry::Function(doc) if doc.name == "Schematic");
+        entries.push(entry);
+        if is_constructor {
+            entries.append(&mut method_docs.0);
+        }
+    }
+    entries
+}
+
+/// The API section of the system message
+pub(super) fn api_docs() -> String {
+    let comment = |text: &str| {
+        text.lines()
+            .map(|line| format!("-- {}\n", line))
+            .collect::<String>()
+    };
+
+    entries()
+        .iter()
+        .map(|entry| match entry {
+            Entry::Section(text) => comment(text),
+            Entry::Function(doc) => {
+                format!(
+                    "{}function {}{}\n",
+                    comment(doc.docs),
+                    doc.name,
+                    doc.signature
+                )
+            }
+            Entry::Method(doc) => format!(
+                "{}function Schematic:{}{}\n",
+                comment(doc.docs),
+                doc.name,
+                doc.signature
+            ),
+            Entry::Value(doc) => format!("{}{}: {}\n", comment(doc.docs), doc.name, doc.signature),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Declares every global binding. All randomness is derived from `seed`.
+pub(super) fn globals<'lua, R: GlobalRegistry<'lua>>(r: &mut R, seed: u32) -> rlua::Result<()> {
+    r.function(
+        doc(
+            "Schematic",
+            "(xSize: number, ySize: number, zSize: number): Schematic",
+            "Creates a schematic\nMax size along any axis is 128",
+        ),
+        |_, (x_size, y_size, z_size): (u8, u8, u8)| {
+            if x_size > 128 || y_size > 128 || z_size > 128 {
+                return Err(RuntimeError(format!(
+                    "schematic size {}x{}x{} too big",
+                    x_size, y_size, z_size
+                )));
+            }
+            Ok(Schematic::new(x_size, y_size, z_size))
+        },
+    )?;
+
+    r.section(
+        "A Color is either a string or a value returned by the color helpers below. Every \
+        function that takes a color accepts both. Strings should be 6-digit hex without the # \
+        such as \"8b4513\", but CSS color names such as \"brown\" are also understood.",
+    );
+    r.function(
+        doc(
+            "rgb",
+            "(r: number, g: number, b: number): Color",
+            "Creates a color from channels between 0 and 255",
+        ),
+        |_, (r, g, b): (u8, u8, u8)| Ok(Color(r, g, b)),
+    )?;
+    r.function(
+        doc(
+            "hsv",
+            "(h: number, s: number, v: number): Color",
+            "Creates a color from a hue in degrees and saturation and value between 0 and 1",
+        ),
+        |_, (h, s, v)| Ok(Color::from_hsv(h, s, v)),
+    )?;
+    r.function(
+        doc(
+            "lerpColor",
+            "(a: Color, b: Color, t: number): Color",
+            "Blends from a to b. t = 0 returns a and t = 1 returns b.",
+        ),
+        |_, (LuaColor(a), LuaColor(b), t): (_, _, f32)| Ok(a.lerp(b, t)),
+    )?;
+    r.function(
+        doc(
+            "shade",
+            "(color: Color, factor: number): Color",
+            "Multiplies the brightness of a color. Below 1 darkens and above 1 lightens.",
+        ),
+        |_, (LuaColor(color), factor): (_, f32)| Ok(color.shade(factor)),
+    )?;
+    r.value(
+        doc(
+            "Colors",
+            "{ [string]: Color }",
+            "Every CSS named color, in lowercase. For example Colors.saddlebrown or \
+            Colors.forestgreen",
+        ),
+        |ctx| {
+            let colors = ctx.create_table()?;
+            for (name, color) in CSS_COLORS {
+                colors.set(name, color)?;
+            }
+            Ok(colors)
+        },
+    )?;
+
+    r.section(
+        "Smooth 3D noise functions for natural looking variation such as terrain, bark or \
+        stone. Scale coordinates down (for example x * 0.1) for larger features.",
+    );
+    let noise = Noise::new(seed as u64);
+    let perlin = noise.clone();
+    r.function(
+        doc(
+            "Perlin",
+            "(x: number, y: number, z: number): number",
+            "Perlin noise in roughly [-1, 1]",
+        ),
+        move |_, (x, y, z)| Ok(perlin.perlin(finite(x)?, finite(y)?, finite(z)?)),
+    )?;
+    let simplex = noise.clone();
+    r.function(
+        doc(
+            "Simplex",
+            "(x: number, y: number, z: number): number",
+            "Simplex noise in roughly [-1, 1]",
+        ),
+        move |_, (x, y, z)| Ok(simplex.simplex(finite(x)?, finite(y)?, finite(z)?)),
+    )?;
+    r.function(
+        doc(
+            "Worley",
+            "(x: number, y: number, z: number): number",
+            "Distance to the nearest random cell point, from 0 to about 1.5",
+        ),
+        move |_, (x, y, z)| Ok(noise.worley(finite(x)?, finite(y)?, finite(z)?)),
+    )?;
+
+    r.section("math.random is already seeded. Do not call math.randomseed.");
+
+    r.section(
+        "Objects with parts that move or can be hidden, like doors, wheels or windmill blades, \
+        should be returned as a table of named schematics instead of a single one, for example \
+        return { frame = frame, door = door }. Tables can be nested to group parts. Every part \
+        is placed in the same coordinate system, so build all of them with the same size to make \
+        them line up.",
+    );
+    r.function(
+        doc(
+            "Animation",
+            "(frames: { Schematic | table }, fps: number?): Animation",
+            "Creates a looping animation such as a spinning windmill or a flickering fire that \
+            shows each frame for 1 / fps seconds. fps defaults to 8. Frames are schematics or \
+            tables of parts and there can be up to 64 of them. An animation can be returned \
+            directly or used as a part, for example \
+            return { tower = tower, blades = Animation({ blades1, blades2, blades3 }) }.",
+        ),
+        |_, (frames, fps): (Vec<LuaModel>, Option<f32>)| {
+            if frames.is_empty() || frames.len() > MAX_FRAMES {
+                return Err(RuntimeError(format!(
+                    "animations must have between 1 and {} frames, not {}",
+                    MAX_FRAMES,
+                    frames.len()
+                )));
+            }
+
+            let fps = fps.unwrap_or(8.);
+            if !(fps > 0. && fps.is_finite()) {
+                return Err(RuntimeError(format!("fps {} must be above 0", fps)));
+            }
+
+            Ok(LuaAnimation(Model::Frames {
+                frames: frames.into_iter().map(|LuaModel(frame)| frame).collect(),
+                seconds_per_frame: 1. / fps,
+            }))
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Noise of infinite or NaN coordinates has no meaning, so it is an error rather than NaN
+fn finite(n: f64) -> rlua::Result<f64> {
+    if n.is_finite() {
+        Ok(n)
+    } else {
+        Err(RuntimeError(format!(
+            "noise coordinate {} is not finite",
+            n
+        )))
+    }
+}
+
+/// Declares every method of `Schematic`
+pub(super) fn schematic_methods<'lua, R: MethodRegistry<'lua>>(r: &mut R) {
+    r.section("Bounds of positions are (0, the size of the axis - 1)");
+    r.method_mut(
+        doc(
+            "Set",
+            "(x: number, y: number, z: number, color: Color)",
+            "Colors a single voxel",
+        ),
+        |_, schematic, (x, y, z, LuaColor(color)): (_, _, _, LuaColor)| match schematic
+            .set(x, y, z, color)
+        {
+            Some(_) => Ok(()),
+            None => Err(RuntimeError(format!(
+                "{}, {}, {} is out of bounds",
+                x, y, z
+            ))),
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Fill",
+            "(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number, color: Color)",
+            "Colors every voxel between two corners (inclusive)",
+        ),
+        |_, schematic, (x1, y1, z1, x2, y2, z2, LuaColor(color)): (_, _, _, _, _, _, LuaColor)| {
+            match schematic.fill(x1, y1, z1, x2, y2, z2, color) {
+                Some(_) => Ok(()),
+                None => Err(RuntimeError(format!(
+                    "fill from {}, {}, {} to {}, {}, {} overlaps an out-of-bounds area",
+                    x1, y1, z1, x2, y2, z2,
+                ))),
+            }
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "DefineMaterial",
+            "(name: string, material: { color: Color, alpha: number?, emissive: number?, \
+            roughness: number?, metallic: number? })",
+            "Declares a named material such as metal, wood, glass or foliage that SetMaterial and \
+            FillMaterial can use.\n\
+            alpha is the opacity from 0 (invisible) to 1 (opaque, the default).\n\
+            emissive is how strongly the voxel glows in its color. 0 (the default) means it \
+            doesn't glow.\n\
+            roughness goes from 0 (mirror-like) to 1 (completely diffuse, the default).\n\
+            metallic goes from 0 (wood, stone, plastic, the default) to 1 (metal).\n\
+            Example: schem:DefineMaterial(\"steel\", { color = \"b0b4b8\", metallic = 1, \
+            roughness = 0.3 })",
+        ),
+        |_, schematic, (name, LuaMaterialDefinition(material)): (String, LuaMaterialDefinition)| {
+            match schematic.define_material(Material {
+                name: name.clone(),
+                ..material
+            }) {
+                Some(_) => Ok(()),
+                None => Err(RuntimeError(format!(
+                    "material {} is already defined or there are too many materials",
+                    name
+                ))),
+            }
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "SetMaterial",
+            "(x: number, y: number, z: number, material: string | { color: Color, alpha: number?, \
+            emissive: number? })",
+            "Like Set, but with a named material or an unnamed material for see-through and \
+            glowing voxels such as glass, water and lanterns. Prefer named materials because they \
+            look more realistic.\n\
+            Example: schem:SetMaterial(2, 3, 4, \"steel\")\n\
+            Example: schem:SetMaterial(2, 3, 4, { color = \"a0d8ef\", alpha = 0.4 })",
+        ),
+        |_, schematic, (x, y, z, material): (_, _, _, LuaMaterial)| {
+            let voxel = material.resolve(schematic)?;
+            match schematic.set_voxel(x, y, z, voxel) {
+                Some(_) => Ok(()),
+                None => Err(RuntimeError(format!(
+                    "{}, {}, {} is out of bounds",
+                    x, y, z
+                ))),
+            }
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "FillMaterial",
+            "(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number, \
+            material: string | { color: Color, alpha: number?, emissive: number? })",
+            "Like Fill, but with a material like SetMaterial",
+        ),
+        |_, schematic, (x1, y1, z1, x2, y2, z2, material): (_, _, _, _, _, _, LuaMaterial)| {
+            let voxel = material.resolve(schematic)?;
+            match schematic.fill_voxel([x1, y1, z1], [x2, y2, z2], voxel) {
+                Some(_) => Ok(()),
+                None => Err(RuntimeError(format!(
+                    "fill from {}, {}, {} to {}, {}, {} overlaps an out-of-bounds area",
+                    x1, y1, z1, x2, y2, z2,
+                ))),
+            }
+        },
+    );
+
+    r.method(
+        doc(
+            "Get",
+            "(x: number, y: number, z: number): string?",
+            "Returns the color at a position as a 6-digit hex string, or nil if it is empty",
+        ),
+        |_, schematic, (x, y, z): (_, _, _)| match schematic.get(x, y, z) {
+            Some(color) => Ok(color.map(Color::to_hex_string)),
+            None => Err(RuntimeError(format!(
+                "{}, {}, {} is out of bounds",
+                x, y, z
+            ))),
+        },
+    );
+
+    r.method(
+        doc(
+            "IsEmpty",
+            "(x: number, y: number, z: number): boolean",
+            "Whether the voxel at a position is empty",
+        ),
+        |_, schematic, (x, y, z): (_, _, _)| match schematic.get(x, y, z) {
+            Some(color) => Ok(color.is_none()),
+            None => Err(RuntimeError(format!(
+                "{}, {}, {} is out of bounds",
+                x, y, z
+            ))),
+        },
+    );
+
+    r.method(
+        doc(
+            "Count",
+            "(color: Color?): number",
+            "Number of voxels of a color, or of all filled voxels if no color is given",
+        ),
+        |_, schematic, color: Option<LuaColor>| Ok(schematic.count(color.map(|c| c.0))),
+    );
+
+    r.method_mut(
+        doc(
+            "Replace",
+            "(oldColor: Color, newColor: Color, x1: number?, y1: number?, z1: number?, \
+            x2: number?, y2: number?, z2: number?): number",
+            "Changes every voxel of one color to another, optionally only between two corners \
+            (inclusive). Returns the number of voxels changed.",
+        ),
+        |_,
+         schematic,
+         (LuaColor(old), LuaColor(new), region): (LuaColor, LuaColor, Variadic<u8>)| {
+            let region = match region[..] {
+                [x1, y1, z1, x2, y2, z2] => Some(([x1, y1, z1], [x2, y2, z2])),
+                [] => None,
+                _ => {
+                    return Err(RuntimeError(
+                        "replace region must have both corners".to_owned(),
+                    ))
+                }
+            };
+
+            schematic.replace(old, new, region).ok_or_else(|| {
+                RuntimeError("replace region overlaps an out-of-bounds area".to_owned())
+            })
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "FloodFill",
+            "(x: number, y: number, z: number, color: Color): number",
+            "Colors the voxel at a position and every voxel connected to it by a face that has \
+            the same contents. Starting from an empty voxel fills the enclosed empty space around \
+            it, such as the inside of a room. Returns the number of voxels changed.",
+        ),
+        |_, schematic, (x, y, z, LuaColor(color)): (_, _, _, LuaColor)| match schematic
+            .flood_fill(x, y, z, color)
+        {
+            Some(filled) => Ok(filled),
+            None => Err(RuntimeError(format!(
+                "{}, {}, {} is out of bounds",
+                x, y, z
+            ))),
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Erase",
+            "(x: number, y: number, z: number)",
+            "Empties a single voxel",
+        ),
+        |_, schematic, (x, y, z): (_, _, _)| match schematic.erase(x, y, z) {
+            Some(_) => Ok(()),
+            None => Err(RuntimeError(format!(
+                "{}, {}, {} is out of bounds",
+                x, y, z
+            ))),
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Carve",
+            "(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number)",
+            "Empties every voxel between two corners (inclusive). Use this to cut doors, windows \
+            and hollow interiors out of solid shapes.",
+        ),
+        |_, schematic, (x1, y1, z1, x2, y2, z2): (_, _, _, _, _, _)| match schematic
+            .carve(x1, y1, z1, x2, y2, z2)
+        {
+            Some(_) => Ok(()),
+            None => Err(RuntimeError(format!(
+                "carve from {}, {}, {} to {}, {}, {} overlaps an out-of-bounds area",
+                x1, y1, z1, x2, y2, z2,
+            ))),
+        },
+    );
+
+    r.section(
+        "Shapes below raise an error if any part of them is out of bounds. When hollow is true, \
+        only a shell one voxel thick is placed.",
+    );
+    r.method_mut(
+        doc(
+            "Sphere",
+            "(x: number, y: number, z: number, radius: number, color: Color, hollow: boolean?)",
+            "Sphere centered at (x, y, z)",
+        ),
+        |_,
+         schematic,
+         (x, y, z, radius, LuaColor(color), hollow): (_, _, _, _, LuaColor, Option<bool>)| {
+            let result = schematic.sphere([x, y, z], radius, color, hollow.unwrap_or(false));
+            shape_result(result, "sphere")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Ellipsoid",
+            "(x: number, y: number, z: number, xRadius: number, yRadius: number, \
+            zRadius: number, color: Color, hollow: boolean?)",
+            "Sphere with a different radius along every axis, centered at (x, y, z)",
+        ),
+        |_,
+         schematic,
+         (x, y, z, x_radius, y_radius, z_radius, LuaColor(color), hollow): (
+            _,
+            _,
+            _,
+            _,
+            _,
+            _,
+            LuaColor,
+            Option<bool>,
+        )| {
+            let radii = [x_radius, y_radius, z_radius];
+            let result = schematic.ellipsoid([x, y, z], radii, color, hollow.unwrap_or(false));
+            shape_result(result, "ellipsoid")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Cylinder",
+            "(x: number, y: number, z: number, radius: number, height: number, color: Color, \
+            hollow: boolean?)",
+            "Vertical cylinder. (x, y, z) is the center of the bottom layer and it extends \
+            upwards.",
+        ),
+        |_,
+         schematic,
+         (x, y, z, radius, height, LuaColor(color), hollow): (
+            _,
+            _,
+            _,
+            _,
+            _,
+            LuaColor,
+            Option<bool>,
+        )| {
+            let hollow = hollow.unwrap_or(false);
+            let result = schematic.cylinder([x, y, z], radius, height, color, hollow);
+            shape_result(result, "cylinder")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Cone",
+            "(x: number, y: number, z: number, radius: number, height: number, color: Color, \
+            hollow: boolean?)",
+            "Vertical cone with its tip at the top. (x, y, z) is the center of the bottom layer.",
+        ),
+        |_,
+         schematic,
+         (x, y, z, radius, height, LuaColor(color), hollow): (
+            _,
+            _,
+            _,
+            _,
+            _,
+            LuaColor,
+            Option<bool>,
+        )| {
+            let hollow = hollow.unwrap_or(false);
+            let result = schematic.cone([x, y, z], radius, height, color, hollow);
+            shape_result(result, "cone")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Pyramid",
+            "(x: number, y: number, z: number, radius: number, height: number, color: Color, \
+            hollow: boolean?)",
+            "Vertical pyramid with a square base. (x, y, z) is the center of the bottom layer \
+            and radius is half the width of the base.",
+        ),
+        |_,
+         schematic,
+         (x, y, z, radius, height, LuaColor(color), hollow): (
+            _,
+            _,
+            _,
+            _,
+            _,
+            LuaColor,
+            Option<bool>,
+        )| {
+            let hollow = hollow.unwrap_or(false);
+            let result = schematic.pyramid([x, y, z], radius, height, color, hollow);
+            shape_result(result, "pyramid")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Torus",
+            "(x: number, y: number, z: number, majorRadius: number, minorRadius: number, \
+            color: Color, hollow: boolean?)",
+            "Ring lying flat in the XZ plane centered at (x, y, z)",
+        ),
+        |_,
+         schematic,
+         (x, y, z, major_radius, minor_radius, LuaColor(color), hollow): (
+            _,
+            _,
+            _,
+            _,
+            _,
+            LuaColor,
+            Option<bool>,
+        )| {
+            let hollow = hollow.unwrap_or(false);
+            let result = schematic.torus([x, y, z], major_radius, minor_radius, color, hollow);
+            shape_result(result, "torus")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Line",
+            "(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number, color: Color)",
+            "Straight line between two points (inclusive)",
+        ),
+        |_, schematic, (x1, y1, z1, x2, y2, z2, LuaColor(color)): (_, _, _, _, _, _, LuaColor)| {
+            let result = schematic.line([x1, y1, z1], [x2, y2, z2], color);
+            shape_result(result, "line")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "HollowBox",
+            "(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number, color: Color)",
+            "Like Fill, but only the walls, floor and ceiling",
+        ),
+        |_, schematic, (x1, y1, z1, x2, y2, z2, LuaColor(color)): (_, _, _, _, _, _, LuaColor)| {
+            let result = schematic.hollow_box([x1, y1, z1], [x2, y2, z2], color);
+            shape_result(result, "box")
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Rotate",
+            "(axis: string, turns: number)",
+            "Rotates the whole schematic counter-clockwise by 90 degrees per turn when looking \
+            down the axis towards the origin. axis is \"x\", \"y\" or \"z\". Negative turns rotate \
+            clockwise. Rotating can swap the sizes of the other two axes.",
+        ),
+        |_, schematic, (axis_str, turns): (String, _)| {
+            schematic.rotate(parse_axis(&axis_str)?, turns);
+            Ok(())
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Mirror",
+            "(axis: string)",
+            "Flips the schematic along an axis",
+        ),
+        |_, schematic, axis_str: String| {
+            schematic.mirror(parse_axis(&axis_str)?);
+            Ok(())
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Shift",
+            "(dx: number, dy: number, dz: number)",
+            "Moves everything by an offset. Voxels moved out of bounds are discarded.",
+        ),
+        |_, schematic, (dx, dy, dz): (_, _, _)| {
+            schematic.shift(dx, dy, dz);
+            Ok(())
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Crop",
+            "()",
+            "Shrinks the schematic to fit its contents exactly",
+        ),
+        |_, schematic, ()| {
+            schematic.crop();
+            Ok(())
+        },
+    );
+
+    r.method(
+        doc(
+            "Copy",
+            "(x1: number, y1: number, z1: number, x2: number, y2: number, z2: number): Schematic",
+            "Returns a new schematic containing a copy of the region between two corners \
+            (inclusive)",
+        ),
+        |_, schematic, (x1, y1, z1, x2, y2, z2): (_, _, _, _, _, _)| match schematic
+            .copy([x1, y1, z1], [x2, y2, z2])
+        {
+            Some(copy) => Ok(copy),
+            None => Err(RuntimeError(format!(
+                "copy from {}, {}, {} to {}, {}, {} overlaps an out-of-bounds area",
+                x1, y1, z1, x2, y2, z2,
+            ))),
+        },
+    );
+
+    r.method_mut(
+        doc(
+            "Paste",
+            "(src: Schematic, x: number, y: number, z: number, mode: string?)",
+            "Places another schematic with its (0, 0, 0) corner at (x, y, z). It must fit \
+            entirely within this schematic. mode is one of:\n\
+            \"overwrite\" (default): filled voxels of src replace what is here\n\
+            \"keep-existing\": filled voxels of src are only placed where this schematic is \
+            empty\n\
+            \"replace-air\": the whole region is replaced, so empty voxels of src also clear this \
+            schematic",
+        ),
+        |_, schematic, (src, x, y, z, mode_str): (Schematic, _, _, _, Option<String>)| {
+            let mode = match mode_str {
+                Some(m) => PasteMode::from_str(&m)
+                    .map_err(|_| RuntimeError(format!("paste mode \"{}\" is invalid", m)))?,
+                None => PasteMode::Overwrite,
+            };
+
+            match schematic.paste(&src, [x, y, z], mode) {
+                Some(_) => Ok(()),
+                None => Err(RuntimeError(format!(
+                    "paste at {}, {}, {} overlaps an out-of-bounds area",
+                    x, y, z
+                ))),
+            }
+        },
+    );
+
+    r.section(
+        "Boolean operations with another schematic placed with its (0, 0, 0) corner at \
+        (x, y, z). It must fit entirely within this schematic. Where both have a voxel, this \
+        schematic's color is kept.",
+    );
+    const CSG_SIGNATURE: &str = "(other: Schematic, x: number, y: number, z: number)";
+    let csg_ops = [
+        (
+            doc("Union", CSG_SIGNATURE, "Adds the voxels of other"),
+            CsgOp::Union,
+        ),
+        (
+            doc(
+                "Difference",
+                CSG_SIGNATURE,
+                "Removes every voxel that other has",
+            ),
+            CsgOp::Difference,
+        ),
+        (
+            doc(
+                "Intersection",
+                CSG_SIGNATURE,
+                "Keeps only voxels that other also has. Everything outside of other is removed.",
+            ),
+            CsgOp::Intersection,
+        ),
+        (
+            doc(
+                "Xor",
+                CSG_SIGNATURE,
+                "Keeps voxels that exactly one of the two has",
+            ),
+            CsgOp::Xor,
+        ),
+    ];
+    for (doc, op) in csg_ops {
+        r.method_mut(
+            doc,
+            move |_, schematic, (other, x, y, z): (Schematic, _, _, _)| match schematic.csg(
+                &other,
+                [x, y, z],
+                op,
+            ) {
+                Some(_) => Ok(()),
+                None => Err(RuntimeError(format!(
+                    "{} at {}, {}, {} overlaps an out-of-bounds area",
+                    doc.name.to_lowercase(),
+                    x,
+                    y,
+                    z
+                ))),
+            },
+        );
+    }
+
+    r.method(
+        doc("xSize", "(): number", "Size along the x axis"),
+        |_, schematic, ()| Ok(schematic.x_size()),
+    );
+    r.method(
+        doc("ySize", "(): number", "Size along the y axis"),
+        |_, schematic, ()| Ok(schematic.y_size()),
+    );
+    r.method(
+        doc("zSize", "(): number", "Size along the z axis"),
+        |_, schematic, ()| Ok(schematic.z_size()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use rlua::{Lua, MetaMethod, StdLib, UserData, UserDataMethods};
+
+    use super::{api_docs, entries, globals, Entry, GlobalRegistrar};
+    use crate::schematic::Schematic;
+
+    /// Records the names of the methods `Schematic` adds to its userdata
+    #[derive(Default)]
+    struct MethodNames(Vec<String>);
+
+    impl MethodNames {
+        fn push(&mut self, name: &[u8]) {
+            self.0.push(String::from_utf8_lossy(name).into_owned());
+        }
+    }
+
+    impl<'lua> UserDataMethods<'lua, Schematic> for MethodNames {
+        fn add_method<S: ?Sized + AsRef<[u8]>, A, R, M>(&mut self, name: &S, _: M) {
+            self.push(name.as_ref());
+        }
+
+        fn add_method_mut<S: ?Sized + AsRef<[u8]>, A, R, M>(&mut self, name: &S, _: M) {
+            self.push(name.as_ref());
+        }
+
+        fn add_function<S: ?Sized + AsRef<[u8]>, A, R, F>(&mut self, name: &S, _: F) {
+            self.push(name.as_ref());
+        }
+
+        fn add_function_mut<S: ?Sized + AsRef<[u8]>, A, R, F>(&mut self, name: &S, _: F) {
+            self.push(name.as_ref());
+        }
+
+        // Metamethods are operators, which the docs don't list
+        fn add_meta_method<A, R, M>(&mut self, _: MetaMethod, _: M) {}
+
+        fn add_meta_method_mut<A, R, M>(&mut self, _: MetaMethod, _: M) {}
+
+        fn add_meta_function<A, R, F>(&mut self, _: MetaMethod, _: F) {}
+
+        fn add_meta_function_mut<A, R, F>(&mut self, _: MetaMethod, _: F) {}
+    }
+
+    /// Methods added to the userdata without going through the registry would be missing from
+    /// the docs
+    #[test]
+    fn test_methods_match_docs() {
+        let mut documented: Vec<_> = entries()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Method(doc) => Some(doc.name.to_owned()),
+                _ => None,
+            })
+            .collect();
+        documented.sort();
+
+        let mut names = MethodNames::default();
+        Schematic::add_methods(&mut names);
+        names.0.sort();
+        assert_eq!(names.0, documented);
+    }
+
+    #[test]
+    fn test_every_binding_is_documented() {
+        let api = api_docs();
+        for entry in entries() {
+            match entry {
+                Entry::Section(text) => assert!(!text.trim().is_empty()),
+                Entry::Function(doc) | Entry::Method(doc) | Entry::Value(doc) => {
+                    assert!(!doc.docs.trim().is_empty(), "{} has no docs", doc.name);
+                    assert!(!doc.signature.is_empty(), "{} has no signature", doc.name);
+                    assert!(api.contains(doc.name), "{} is missing", doc.name);
+                }
+            }
+        }
+    }
+
+    /// Globals set without going through the registry would be missing from the docs
+    #[test]
+    fn test_globals_match_docs() {
+        let mut documented: Vec<_> = entries()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Function(doc) | Entry::Value(doc) => Some(doc.name.to_owned()),
+                _ => None,
+            })
+            .collect();
+        documented.sort();
+
+        let lua = Lua::new_with(StdLib::MATH);
+        lua.context(|ctx| {
+            let names = || -> Vec<String> {
+                ctx.globals()
+                    .pairs::<String, rlua::Value>()
+                    .map(|pair| pair.unwrap().0)
+                    .collect()
+            };
+            let builtins = names();
+            globals(&mut GlobalRegistrar(ctx), 0).unwrap();
+
+            let mut registered: Vec<_> = names()
+                .into_iter()
+                .filter(|name| !builtins.contains(name))
+                .collect();
+            registered.sort();
+            assert_eq!(registered, documented);
+
+            for entry in entries() {
+                if let Entry::Method(doc) = entry {
+                    let method: rlua::Value = ctx
+                        .load(&format!("return Schematic(1, 1, 1).{}", doc.name))
+                        .eval()
+                        .unwrap();
+                    assert!(
+                        matches!(method, rlua::Value::Function(_)),
+                        "{} is not a method",
+                        doc.name
+                    );
+                }
+            }
+        });
+    }
+}
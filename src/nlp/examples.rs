@@ -0,0 +1,256 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use strum_macros::EnumString;
+
+use crate::search::{self, Embedder};
+
+use super::{Message, Usage};
+
+/// A prompt and Lua code that answers it well, shown to the model before the actual prompt
+#[derive(Clone, Debug)]
+pub struct Example {
+    pub name: String,
+    pub prompt: String,
+    pub tags: Vec<String>,
+    pub code: String,
+    /// Embedding of the prompt, only computed for `Selector::Similar`
+    embedding: Option<Vec<f32>>,
+}
+
+impl Example {
+    /// Parses an example file. The file starts with `-- prompt: ...` and optionally
+    /// `-- tags: a, b` comment lines, and the rest is the code.
+    fn parse(name: &str, source: &str) -> Option<Example> {
+        let mut prompt = None;
+        let mut tags = Vec::new();
+        let mut lines = source.lines().peekable();
+        while let Some(line) = lines.peek() {
+            if let Some(p) = line.strip_prefix("-- prompt:") {
+                prompt = Some(p.trim().to_owned());
+            } else if let Some(t) = line.strip_prefix("-- tags:") {
+                tags = t
+                    .split(',')
+                    .map(|tag| tag.trim().to_lowercase())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+            } else {
+                break;
+            }
+            lines.next();
+        }
+
+        Some(Example {
+            name: name.to_owned(),
+            prompt: prompt?,
+            tags,
+            code: lines.collect::<Vec<_>>().join("\n").trim().to_owned(),
+            embedding: None,
+        })
+    }
+}
+
+/// How examples are picked for a prompt
+#[derive(Clone, Copy, Debug, EnumString, Hash, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum Selector {
+    /// Any examples, picked with the seed of the generation
+    Random,
+    /// Examples with the most tags that appear in the prompt
+    Tags,
+    /// Examples with the most similar prompts. Falls back to tags without an embedding.
+    Similar,
+}
+
+/// Curated examples loaded from `*.lua` files in a directory
+pub struct ExampleLibrary {
+    examples: Vec<Example>,
+    selector: Selector,
+    /// Number of examples given with every prompt
+    count: usize,
+}
+
+impl ExampleLibrary {
+    /// Loads every example in `dir` and executes it to make sure it still works with the current
+    /// API. A missing directory gives an empty library.
+    pub fn load(
+        dir: &Path,
+        selector: Selector,
+        count: usize,
+    ) -> Result<ExampleLibrary, Box<dyn std::error::Error>> {
+        let mut examples = Vec::new();
+        if dir.is_dir() {
+            let mut paths: Vec<_> = std::fs::read_dir(dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            paths.retain(|p| p.extension().and_then(|e| e.to_str()) == Some("lua"));
+            paths.sort();
+
+            for path in paths {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                let source = std::fs::read_to_string(&path)?;
+                let example = Example::parse(&name, &source)
+                    .ok_or_else(|| format!("example {} has no prompt", name))?;
+                if let Err(e) = super::execute(&example.code, 0) {
+                    return Err(format!("example {} failed: {}", name, e).into());
+                }
+                examples.push(example);
+            }
+        }
+
+        tracing::info!("loaded {} examples", examples.len());
+        Ok(ExampleLibrary {
+            examples,
+            selector,
+            count,
+        })
+    }
+
+    pub fn selector(&self) -> Selector {
+        self.selector
+    }
+
+    /// Embeds the prompt of every example, which `Selector::Similar` needs
+    pub async fn embed(
+        &mut self,
+        embedder: &dyn Embedder,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut usage = Usage::default();
+        for example in &mut self.examples {
+            example.embedding = Some(embedder.embed(&example.prompt, &mut usage).await?);
+        }
+        tracing::info!("embedded examples using {} tokens", usage.total_tokens);
+        Ok(())
+    }
+
+    /// Changes whenever the examples or the way they are selected change
+    pub fn version(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for example in &self.examples {
+            (&example.prompt, &example.code).hash(&mut hasher);
+        }
+        (self.selector, self.count).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Picks the examples for `prompt`, most relevant first. `embedding` is the embedding of the
+    /// prompt and only used by `Selector::Similar`.
+    pub fn select(&self, prompt: &str, embedding: Option<&[f32]>, seed: u32) -> Vec<&Example> {
+        // Shuffling first picks randomly among equally good examples
+        let mut examples: Vec<_> = self.examples.iter().collect();
+        examples.shuffle(&mut StdRng::seed_from_u64(seed as u64));
+
+        match (self.selector, embedding) {
+            (Selector::Random, _) => {}
+            (Selector::Similar, Some(embedding)) => {
+                let similarity = |e: &Example| {
+                    e.embedding
+                        .as_ref()
+                        .and_then(|v| search::similarity(v, embedding))
+                        .unwrap_or(-1.)
+                };
+                examples.sort_by(|a, b| similarity(b).total_cmp(&similarity(a)));
+            }
+            (Selector::Tags | Selector::Similar, _) => {
+                let words: Vec<_> = prompt
+                    .to_lowercase()
+                    .split(|c: char| !c.is_alphanumeric())
+                    .map(|w| w.trim_end_matches('s').to_owned())
+                    .collect();
+                let matches = |e: &Example| {
+                    e.tags
+                        .iter()
+                        .filter(|tag| words.iter().any(|w| w == tag.trim_end_matches('s')))
+                        .count()
+                };
+                examples.retain(|e| matches(e) > 0);
+                examples.sort_by_key(|e| std::cmp::Reverse(matches(e)));
+            }
+        }
+
+        examples.truncate(self.count);
+        examples
+    }
+}
+
+/// Turns examples into earlier turns of the conversation
+pub(super) fn messages(examples: &[&Example]) -> Vec<Message> {
+    examples
+        .iter()
+        .flat_map(|e| [Message::user(&e.prompt), Message::assistant(&e.code)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Example, ExampleLibrary, Selector};
+
+    fn library(selector: Selector, count: usize) -> ExampleLibrary {
+        let examples = [
+            ("cabin", "a small cabin", "building, house, wood"),
+            ("oak", "an oak tree", "tree, plant"),
+            ("pine", "a pine tree in the snow", "tree, winter"),
+        ];
+        ExampleLibrary {
+            examples: examples
+                .iter()
+                .map(|(name, prompt, tags)| {
+                    let source = format!("-- prompt: {}\n-- tags: {}\nreturn 1", prompt, tags);
+                    Example::parse(name, &source).unwrap()
+                })
+                .collect(),
+            selector,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let example =
+            Example::parse("hut", "-- prompt: a hut \n-- tags: House, \n\nreturn hut\n").unwrap();
+        assert_eq!(example.prompt, "a hut");
+        assert_eq!(example.tags, ["house"]);
+        assert_eq!(example.code, "return hut");
+        assert!(Example::parse("hut", "return hut").is_none());
+    }
+
+    #[test]
+    fn test_select_tags() {
+        let library = library(Selector::Tags, 2);
+        let names = |examples: Vec<&Example>| -> Vec<String> {
+            examples.into_iter().map(|e| e.name.clone()).collect()
+        };
+
+        assert_eq!(names(library.select("a wooden house", None, 1)), ["cabin"]);
+        let trees = names(library.select("some trees in winter", None, 1));
+        assert_eq!(trees, ["pine", "oak"]);
+        assert!(library.select("a car", None, 1).is_empty());
+    }
+
+    #[test]
+    fn test_select_random() {
+        let library = library(Selector::Random, 2);
+        assert_eq!(library.select("a car", None, 3).len(), 2);
+        let pick = |seed| {
+            library
+                .select("a car", None, seed)
+                .iter()
+                .map(|e| e.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pick(7), pick(7));
+    }
+
+    /// Every shipped example must run with the current API
+    #[test]
+    fn test_load_examples() {
+        let library = ExampleLibrary::load(Path::new("examples"), Selector::Random, 3).unwrap();
+        assert!(!library.examples.is_empty());
+    }
+}
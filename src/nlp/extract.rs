@@ -0,0 +1,83 @@
+/// Pulls Lua code out of a free-text reply. Fenced code blocks tagged as Lua or not tagged at all
+/// are joined in order, so code split over several blocks still works and prose around them is
+/// dropped. Blocks tagged with other languages are only used if there is nothing else, and a reply
+/// without any fences is assumed to be code.
+pub(super) fn extract_code(reply: &str) -> String {
+    let mut lua_blocks = Vec::new();
+    let mut other_blocks = Vec::new();
+
+    let mut current: Option<(bool, Vec<&str>)> = None;
+    for line in reply.lines() {
+        let fence = line.trim().strip_prefix("```");
+        match (&mut current, fence) {
+            (None, Some(tag)) => {
+                let tag = tag.trim();
+                current = Some((
+                    tag.is_empty() || tag.eq_ignore_ascii_case("lua"),
+                    Vec::new(),
+                ));
+            }
+            (Some(_), Some(_)) => {
+                let (is_lua, lines) = current.take().unwrap();
+                if is_lua {
+                    lua_blocks.push(lines.join("\n"));
+                } else {
+                    other_blocks.push(lines.join("\n"));
+                }
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, None) => {}
+        }
+    }
+
+    // Replies cut off by the token limit have no closing fence
+    if let Some((is_lua, lines)) = current {
+        if is_lua {
+            lua_blocks.push(lines.join("\n"));
+        } else {
+            other_blocks.push(lines.join("\n"));
+        }
+    }
+
+    if !lua_blocks.is_empty() {
+        lua_blocks.join("\n")
+    } else if !other_blocks.is_empty() {
+        other_blocks.join("\n")
+    } else {
+        reply.trim().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_code;
+
+    #[test]
+    fn test_extract_code() {
+        let code = "local s = Schematic(1, 1, 1)\nreturn s";
+        let replies = [
+            code.to_owned(),
+            format!("\n  {}  \n\n", code),
+            format!("```lua\n{}\n```", code),
+            format!("```lua\n{}\n```\n  \n", code),
+            format!("```Lua\n{}\n```", code),
+            format!("```\n{}\n```", code),
+            format!(
+                "Here is the code:\n\n```lua\n{}\n```\nIt builds a voxel.",
+                code
+            ),
+            format!("```lua\n{}\n", code),
+            "```lua\nlocal s = Schematic(1, 1, 1)\n```\nThen return it:\n```lua\nreturn s\n```"
+                .to_owned(),
+            format!(
+                "```json\n{{ \"plan\": \"one voxel\" }}\n```\n```lua\n{}\n```",
+                code
+            ),
+            format!("```text\n{}\n```", code),
+        ];
+
+        for reply in replies {
+            assert_eq!(extract_code(&reply), code, "{}", reply);
+        }
+    }
+}
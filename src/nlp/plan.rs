@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::color::{Color, ParseMode};
+
+use super::Tool;
+
+/// Largest size of a schematic along any axis
+pub(super) const MAX_SIZE: u32 = 128;
+
+/// Structured description of what to build, written before any code
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Plan {
+    pub size: [u32; 3],
+    pub palette: Vec<PaletteEntry>,
+    pub components: Vec<Component>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PaletteEntry {
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Component {
+    pub name: String,
+    pub description: String,
+    /// Inclusive minimum corner
+    pub from: [u32; 3],
+    /// Inclusive maximum corner
+    pub to: [u32; 3],
+    /// Name of a palette entry
+    pub color: String,
+}
+
+impl Plan {
+    /// Checks that the plan can be built, returning a description of the first problem found
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size.iter().any(|&s| s == 0 || s > MAX_SIZE) {
+            return Err(format!(
+                "size {:?} must be between 1 and {} along every axis",
+                self.size, MAX_SIZE
+            ));
+        }
+
+        for entry in &self.palette {
+            if Color::parse(&entry.color, ParseMode::Lenient).is_err() {
+                return Err(format!(
+                    "palette entry {} has invalid color {}",
+                    entry.name, entry.color
+                ));
+            }
+        }
+
+        for component in &self.components {
+            if (0..3)
+                .any(|i| component.from[i] > component.to[i] || component.to[i] >= self.size[i])
+            {
+                return Err(format!(
+                    "component {} from {:?} to {:?} is outside of size {:?}",
+                    component.name, component.from, component.to, self.size
+                ));
+            }
+
+            if !self.palette.iter().any(|e| e.name == component.color) {
+                return Err(format!(
+                    "component {} uses color {}, which is not in the palette",
+                    component.name, component.color
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn tool() -> Tool {
+        let point = json!({
+            "type": "array",
+            "items": { "type": "integer", "minimum": 0 },
+            "minItems": 3,
+            "maxItems": 3,
+        });
+
+        Tool {
+            name: "submit_plan",
+            description: "Submits the plan for the voxel art",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "size": {
+                        "description": "Size along x, y and z",
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 1, "maximum": MAX_SIZE },
+                        "minItems": 3,
+                        "maxItems": 3,
+                    },
+                    "palette": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "color": { "type": "string", "description": "6-digit hex color" },
+                            },
+                            "required": ["name", "color"],
+                        },
+                    },
+                    "components": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "description": { "type": "string" },
+                                "from": point,
+                                "to": point,
+                                "color": { "type": "string", "description": "Palette entry name" },
+                            },
+                            "required": ["name", "description", "from", "to", "color"],
+                        },
+                    },
+                },
+                "required": ["size", "palette", "components"],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Component, PaletteEntry, Plan};
+
+    fn plan() -> Plan {
+        Plan {
+            size: [64, 32, 64],
+            palette: vec![PaletteEntry {
+                name: "stone".to_owned(),
+                color: "808080".to_owned(),
+            }],
+            components: vec![Component {
+                name: "keep".to_owned(),
+                description: "Central tower".to_owned(),
+                from: [24, 0, 24],
+                to: [39, 31, 39],
+                color: "stone".to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(plan().validate(), Ok(()));
+
+        let mut too_big = plan();
+        too_big.size = [64, 129, 64];
+        assert!(too_big.validate().is_err());
+
+        let mut empty = plan();
+        empty.size = [0, 32, 64];
+        assert!(empty.validate().is_err());
+
+        let mut outside = plan();
+        outside.components[0].to = [39, 32, 39];
+        assert!(outside.validate().is_err());
+
+        let mut inverted = plan();
+        inverted.components[0].from = [40, 0, 24];
+        assert!(inverted.validate().is_err());
+
+        let mut unknown_color = plan();
+        unknown_color.components[0].color = "marble".to_owned();
+        assert!(unknown_color.validate().is_err());
+
+        let mut invalid_color = plan();
+        invalid_color.palette[0].color = "stone gray".to_owned();
+        assert!(invalid_color.validate().is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let json = r#"{
+            "size": [64, 32, 64],
+            "palette": [{ "name": "stone", "color": "808080" }],
+            "components": [{
+                "name": "keep",
+                "description": "Central tower",
+                "from": [24, 0, 24],
+                "to": [39, 31, 39],
+                "color": "stone"
+            }]
+        }"#;
+        assert_eq!(serde_json::from_str::<Plan>(json).unwrap(), plan());
+    }
+}
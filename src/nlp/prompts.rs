@@ -0,0 +1,151 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use super::{bindings, plan};
+
+/// Prompt templates loaded from `<dir>/<version>/`. Templates are plain text in which
+/// `{{name}}` is replaced by the variable `name`. Every template is rendered when it is loaded, so
+/// a typo in a variable name is reported before any request uses it.
+#[derive(Debug)]
+pub struct Prompts {
+    dir: PathBuf,
+    /// Name of the directory the templates were loaded from, such as `v1`
+    pub version: String,
+    /// System message for writing code. `{{api}}` is the generated Lua API documentation.
+    system: String,
+    /// System message for planning
+    plan_system: String,
+    /// User message with a plan, with `{{prompt}}` and `{{plan}}`
+    follow_plan: String,
+    /// User message asking to change code, with `{{instruction}}`
+    edit: String,
+}
+
+impl Prompts {
+    pub fn load(dir: &Path, version: &str) -> Result<Prompts, Box<dyn std::error::Error>> {
+        // The version comes from a request when reloading, so it must not leave `dir`
+        if version.is_empty()
+            || !version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            || version.starts_with('.')
+        {
+            return Err(format!("prompt version \"{}\" is invalid", version).into());
+        }
+
+        let read = |name: &str| -> Result<String, Box<dyn std::error::Error>> {
+            let path = dir.join(version).join(format!("{}.txt", name));
+            match std::fs::read_to_string(&path) {
+                Ok(source) => Ok(source.trim_end().to_owned()),
+                Err(e) => Err(format!("failed to read {}: {}", path.display(), e).into()),
+            }
+        };
+
+        let max_size = plan::MAX_SIZE.to_string();
+        let prompts = Prompts {
+            dir: dir.to_owned(),
+            version: version.to_owned(),
+            system: render(&read("system")?, &[("api", &bindings::api_docs())])?,
+            plan_system: render(&read("plan")?, &[("max_size", &max_size)])?,
+            follow_plan: read("follow_plan")?,
+            edit: read("edit")?,
+        };
+        render(&prompts.follow_plan, &[("prompt", ""), ("plan", "")])?;
+        render(&prompts.edit, &[("instruction", "")])?;
+        Ok(prompts)
+    }
+
+    /// Loads `version` from the same directory, or the current version again if it is `None`
+    pub fn reload(&self, version: Option<&str>) -> Result<Prompts, Box<dyn std::error::Error>> {
+        Prompts::load(&self.dir, version.unwrap_or(&self.version))
+    }
+
+    /// Changes whenever the content of a template changes, even if the version doesn't
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (
+            &self.system,
+            &self.plan_system,
+            &self.follow_plan,
+            &self.edit,
+        )
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub(super) fn system(&self) -> &str {
+        &self.system
+    }
+
+    pub(super) fn plan_system(&self) -> &str {
+        &self.plan_system
+    }
+
+    pub(super) fn follow_plan(&self, prompt: &str, plan: &str) -> String {
+        render(&self.follow_plan, &[("prompt", prompt), ("plan", plan)])
+            .expect("validated when loaded")
+    }
+
+    pub(super) fn edit(&self, instruction: &str) -> String {
+        render(&self.edit, &[("instruction", instruction)]).expect("validated when loaded")
+    }
+}
+
+/// Replaces every `{{name}}` in `template`. Values are inserted as they are, so they may contain
+/// braces.
+fn render(template: &str, variables: &[(&str, &str)]) -> Result<String, String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => return Err("template has an unclosed {{".to_owned()),
+        };
+
+        let name = rest[start + 2..end].trim();
+        match variables.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => return Err(format!("template variable {} is unknown", name)),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{render, Prompts};
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(
+                "size {{ size }}, {{name}}!",
+                &[("name", "{{x}}"), ("size", "3")]
+            ),
+            Ok("size 3, {{x}}!".to_owned())
+        );
+        assert!(render("{{missing}}", &[]).is_err());
+        assert!(render("{{size", &[("size", "3")]).is_err());
+    }
+
+    #[test]
+    fn test_load_prompts() {
+        let prompts = Prompts::load(Path::new("prompts"), "v1").unwrap();
+        assert!(prompts.system().contains("function Schematic:Fill("));
+        assert!(prompts.plan_system().contains("larger than 128"));
+        assert!(prompts.edit("make it red").contains("make it red"));
+
+        assert!(Prompts::load(Path::new("prompts"), "../prompts/v1").is_err());
+        assert!(prompts.reload(Some("missing")).is_err());
+        assert_eq!(
+            prompts.reload(None).unwrap().fingerprint(),
+            prompts.fingerprint()
+        );
+    }
+}
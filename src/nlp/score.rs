@@ -0,0 +1,86 @@
+use serde::Serialize;
+
+use crate::schematic::{Model, Stats};
+
+/// How good a sample looks, judged only by heuristics on its output
+#[derive(Clone, Debug, Serialize)]
+pub struct Score {
+    /// From 0 to 1. Samples that fail to execute or are empty score 0.
+    pub total: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Score {
+    pub(super) fn of(result: &rlua::Result<Model>) -> Score {
+        match result {
+            Ok(model) => {
+                let stats = model.stats();
+                Score {
+                    total: rate(&stats),
+                    stats: Some(stats),
+                    error: None,
+                }
+            }
+            Err(e) => Score {
+                total: 0.,
+                stats: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+fn rate(stats: &Stats) -> f32 {
+    if stats.voxels == 0 {
+        return 0.;
+    }
+
+    // Tiny models are usually a failed attempt at something bigger
+    let size = (stats.voxels as f32 / 200.).min(1.);
+    // A single color is rarely what was asked for
+    let colors = (stats.colors as f32 / 4.).min(1.);
+    // Solid blocks and a few scattered voxels both score low
+    let fill = (1. - (stats.fill - 0.4).abs() / 0.6).max(0.);
+    let grounded = 1. - stats.floating;
+
+    0.3 * size + 0.2 * colors + 0.2 * fill + 0.3 * grounded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schematic::Stats;
+
+    use super::rate;
+
+    #[test]
+    fn test_rate() {
+        let good = Stats {
+            voxels: 500,
+            colors: 6,
+            fill: 0.4,
+            floating: 0.,
+        };
+        assert_eq!(rate(&good), 1.);
+
+        let empty = Stats {
+            voxels: 0,
+            colors: 0,
+            fill: 0.,
+            floating: 0.,
+        };
+        assert_eq!(rate(&empty), 0.);
+
+        let solid = Stats { fill: 1., ..good };
+        let floating = Stats {
+            floating: 0.5,
+            ..good
+        };
+        let plain = Stats { colors: 1, ..good };
+        for worse in [solid, floating, plain] {
+            assert!(rate(&worse) < rate(&good), "{:?}", worse);
+        }
+    }
+}
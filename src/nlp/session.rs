@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::Message;
+
+/// Context window of the model in tokens
+const CONTEXT_TOKENS: usize = 8192;
+
+/// Conversation history of a multi-turn design session. Every turn is a prompt followed by the
+/// code generated for it and, if the code failed, the error it produced.
+#[derive(Clone, Default)]
+pub struct Session {
+    messages: Vec<Message>,
+}
+
+impl Session {
+    pub(super) fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// The most recent turns that fit into `budget` tokens. The latest turn is always included and
+    /// turns are never split, so the result always starts with a prompt.
+    pub(super) fn recent(&self, budget: usize) -> &[Message] {
+        let mut used = 0;
+        let mut start = self.messages.len();
+        for (i, message) in self.messages.iter().enumerate().rev() {
+            used += estimate_tokens(&message.content);
+            if message.role == "user" && !message.content.starts_with(ERROR_PREFIX) {
+                if used > budget && start < self.messages.len() {
+                    break;
+                }
+                start = i;
+            }
+        }
+        &self.messages[start..]
+    }
+
+    /// Records a turn that was answered without asking the model, such as a cached response
+    pub fn push_turn(&mut self, prompt: &str, code: &str) {
+        self.messages.push(Message::user(prompt));
+        self.messages.push(Message::assistant(code));
+    }
+
+    /// Records that the code generated for the last prompt failed so the next turn can fix it
+    pub(super) fn push_error(&mut self, error: &str) {
+        self.messages
+            .push(Message::user(&format!("{}{}", ERROR_PREFIX, error)));
+    }
+}
+
+/// Sessions kept at most. When full, expired sessions are dropped first and then the ones used
+/// longest ago.
+const MAX_SESSIONS: usize = 10_000;
+
+/// Design sessions by id. Sessions are only kept in memory, so they are lost on restart, and
+/// expire `ttl` after they were last used.
+pub struct SessionStore {
+    ttl: Duration,
+    sessions: Mutex<HashMap<String, (Instant, Session)>>,
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        SessionStore {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(id) {
+            Some((used, session)) if used.elapsed() < self.ttl => {
+                *used = Instant::now();
+                Some(session.clone())
+            }
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, id: String, session: Session) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= MAX_SESSIONS && !sessions.contains_key(&id) {
+            sessions.retain(|_, (used, _)| used.elapsed() < self.ttl);
+        }
+        while sessions.len() >= MAX_SESSIONS && !sessions.contains_key(&id) {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(id, _)| id.clone());
+            match oldest {
+                Some(oldest) => sessions.remove(&oldest),
+                None => break,
+            };
+        }
+        sessions.insert(id, (Instant::now(), session));
+    }
+}
+
+const ERROR_PREFIX: &str = "That code failed with the following error: ";
+
+/// Tokens available for the history after the system message and the response
+pub(super) fn history_budget(system_message: &str, max_response_tokens: usize) -> usize {
+    CONTEXT_TOKENS.saturating_sub(estimate_tokens(system_message) + max_response_tokens)
+}
+
+/// Rough token count. English text and code average about 4 characters per token, and every
+/// message has a few tokens of overhead.
+pub(super) fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4 + 4
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nlp::Message;
+
+    use std::time::Duration;
+
+    use super::{estimate_tokens, Session, SessionStore, MAX_SESSIONS};
+
+    #[test]
+    fn test_recent() {
+        let mut session = Session::default();
+        assert!(session.recent(1000).is_empty());
+
+        session.push(Message::user(&"a".repeat(400)));
+        session.push(Message::assistant(&"b".repeat(400)));
+        session.push_error("attempt to index a nil value");
+        session.push(Message::user("now add a garden"));
+        session.push(Message::assistant(&"c".repeat(400)));
+
+        assert_eq!(session.recent(10000).len(), 5);
+
+        // The last turn fits, but the one before it doesn't completely, so it is dropped
+        let last_turn = estimate_tokens("now add a garden") + estimate_tokens(&"c".repeat(400));
+        let recent = session.recent(last_turn + 150);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].content, "now add a garden");
+
+        // The latest turn is kept even if it doesn't fit
+        assert_eq!(session.recent(0).len(), 2);
+    }
+
+    #[test]
+    fn test_store() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        for i in 0..MAX_SESSIONS {
+            store.insert(i.to_string(), Session::default());
+        }
+        assert!(store.get("0").is_some());
+
+        // The least recently used session is evicted, which isn't the one that was just used
+        store.insert("new".to_owned(), Session::default());
+        assert_eq!(store.sessions.lock().unwrap().len(), MAX_SESSIONS);
+        assert!(store.get("0").is_some());
+        assert!(store.get("new").is_some());
+
+        let expired = SessionStore::new(Duration::ZERO);
+        expired.insert("a".to_owned(), Session::default());
+        assert!(expired.get("a").is_none());
+    }
+}
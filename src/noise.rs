@@ -0,0 +1,220 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Seeded 3D coherent noise. The same seed always produces the same values. Lattice coordinates
+/// wrap around, so coordinates beyond the range of `i32` give meaningless values instead of
+/// overflowing.
+#[derive(Clone)]
+pub struct Noise {
+    perm: [u8; 512],
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let mut perm = [0; 512];
+        for (i, p) in perm.iter_mut().enumerate() {
+            *p = table[i % 256];
+        }
+        Noise { perm }
+    }
+
+    fn hash(&self, x: i32, y: i32, z: i32) -> u8 {
+        let x = self.perm[(x & 255) as usize] as usize;
+        let y = self.perm[x + (y & 255) as usize] as usize;
+        self.perm[y + (z & 255) as usize]
+    }
+
+    /// Improved Perlin noise in roughly [-1, 1]. Integer coordinates always return 0, so inputs
+    /// should be scaled down to get smooth variation.
+    pub fn perlin(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (xi, yi, zi) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+        let (xf, yf, zf) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let hash = self.hash(
+                xi.wrapping_add(dx),
+                yi.wrapping_add(dy),
+                zi.wrapping_add(dz),
+            );
+            gradient(hash, xf - dx as f64, yf - dy as f64, zf - dz as f64)
+        };
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Simplex noise in roughly [-1, 1]. Has fewer directional artifacts than Perlin noise.
+    pub fn simplex(&self, x: f64, y: f64, z: f64) -> f64 {
+        const F3: f64 = 1. / 3.;
+        const G3: f64 = 1. / 6.;
+
+        // Skew into simplex space to find the containing cell
+        let s = (x + y + z) * F3;
+        let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+        let t = (i + j + k) * G3;
+        let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+
+        // Find which of the six tetrahedra the point is in
+        let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+        let corners = [
+            (0, 0, 0, 0.),
+            (i1, j1, k1, G3),
+            (i2, j2, k2, 2. * G3),
+            (1, 1, 1, 3. * G3),
+        ];
+
+        let total: f64 = corners
+            .iter()
+            .map(|&(di, dj, dk, offset)| {
+                let (dx, dy, dz) = (
+                    x0 - di as f64 + offset,
+                    y0 - dj as f64 + offset,
+                    z0 - dk as f64 + offset,
+                );
+                let falloff = 0.6 - dx * dx - dy * dy - dz * dz;
+                if falloff < 0. {
+                    return 0.;
+                }
+                let hash = self.hash(i.wrapping_add(di), j.wrapping_add(dj), k.wrapping_add(dk));
+                falloff.powi(4) * gradient(hash, dx, dy, dz)
+            })
+            .sum();
+
+        32. * total
+    }
+
+    /// Worley (cellular) noise: the distance from the point to the nearest of a set of random
+    /// feature points, one per unit cell. Ranges from 0 to about 1.5.
+    pub fn worley(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (xi, yi, zi) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+        let mut nearest = f64::MAX;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let (cx, cy, cz) = (
+                        xi.wrapping_add(dx),
+                        yi.wrapping_add(dy),
+                        zi.wrapping_add(dz),
+                    );
+                    let feature = [
+                        cx as f64 + self.hash(cx, cy, cz) as f64 / 255.,
+                        cy as f64 + self.hash(cx.wrapping_add(17), cy, cz) as f64 / 255.,
+                        cz as f64 + self.hash(cx, cy.wrapping_add(31), cz) as f64 / 255.,
+                    ];
+                    let distance = (feature[0] - x).powi(2)
+                        + (feature[1] - y).powi(2)
+                        + (feature[2] - z).powi(2);
+                    nearest = nearest.min(distance);
+                }
+            }
+        }
+
+        nearest.sqrt()
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product with one of 12 gradient directions selected by the hash
+fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Noise;
+
+    fn samples() -> impl Iterator<Item = (f64, f64, f64)> {
+        (0..1000).map(|i| {
+            let i = i as f64;
+            (i * 0.37, i * 0.11 - 20., i * 0.53 + 3.)
+        })
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let (a, b, c) = (Noise::new(1), Noise::new(1), Noise::new(2));
+        for (x, y, z) in samples() {
+            assert_eq!(a.perlin(x, y, z), b.perlin(x, y, z));
+            assert_eq!(a.simplex(x, y, z), b.simplex(x, y, z));
+            assert_eq!(a.worley(x, y, z), b.worley(x, y, z));
+        }
+
+        assert!(samples().any(|(x, y, z)| a.perlin(x, y, z) != c.perlin(x, y, z)));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let noise = Noise::new(1234);
+        for (x, y, z) in samples() {
+            assert!((-1.1..=1.1).contains(&noise.perlin(x, y, z)));
+            assert!((-1.1..=1.1).contains(&noise.simplex(x, y, z)));
+            assert!((0. ..=1.8).contains(&noise.worley(x, y, z)));
+        }
+
+        assert_eq!(noise.perlin(3., -4., 5.), 0.);
+    }
+
+    #[test]
+    fn test_extreme_coordinates() {
+        let noise = Noise::new(1);
+        for x in [
+            f64::MAX,
+            f64::MIN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ] {
+            noise.perlin(x, x, x);
+            noise.simplex(x, x, x);
+            noise.worley(x, x, x);
+        }
+        assert!(noise.perlin(f64::MAX, 0.5, 0.5).is_finite());
+    }
+}
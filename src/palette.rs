@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Compact storage for a fixed number of optional values. Every distinct value is stored once in a
+/// palette and each slot only holds a bit-packed index into it, using as few bits as the palette
+/// size allows. Index 0 always means "empty".
+#[derive(Clone, Debug)]
+pub struct PalettedVec<T> {
+    palette: Vec<T>,
+    /// Position in `palette` of every value that is in use
+    positions: HashMap<T, usize>,
+    /// Number of slots using each palette entry
+    counts: Vec<usize>,
+    /// Palette entries no longer used by any slot. New values take these before the palette
+    /// grows, so overwritten values don't widen the indices.
+    free: Vec<usize>,
+    bits: u32,
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl<T: Copy + Eq + Hash> PalettedVec<T> {
+    pub fn new(len: usize) -> Self {
+        PalettedVec {
+            palette: Vec::new(),
+            positions: HashMap::new(),
+            counts: Vec::new(),
+            free: Vec::new(),
+            bits: 1,
+            len,
+            words: vec![0; word_count(len, 1)],
+        }
+    }
+
+    /// Distinct values that have been stored. Slot indices returned by `palette_index` are offset by
+    /// one into this slice. Entries that no slot uses any more may still be in it until a new
+    /// value takes their place.
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    /// Panics if `index` is out of range
+    pub fn get(&self, index: usize) -> Option<T> {
+        match self.palette_index(index) {
+            0 => None,
+            i => Some(self.palette[i - 1]),
+        }
+    }
+
+    /// Panics if `index` is out of range
+    pub fn set(&mut self, index: usize, value: Option<T>) {
+        let old = self.palette_index(index);
+        let new = match value {
+            Some(v) => self.palette_index_of(v),
+            None => 0,
+        };
+        if new == old {
+            return;
+        }
+
+        if new != 0 {
+            self.counts[new - 1] += 1;
+        }
+        if old != 0 {
+            self.release(old);
+        }
+        self.write(index, new);
+    }
+
+    /// Returns the raw palette index of a slot, where 0 is empty and `n` refers to
+    /// `palette()[n - 1]`. Panics if `index` is out of range
+    pub fn palette_index(&self, index: usize) -> usize {
+        assert!(index < self.len, "index {} out of range", index);
+        let per_word = entries_per_word(self.bits);
+        let word = self.words[index / per_word];
+        let shift = (index % per_word) as u32 * self.bits;
+        ((word >> shift) & mask(self.bits)) as usize
+    }
+
+    /// Finds or adds the entry of `value` without counting a use of it
+    fn palette_index_of(&mut self, value: T) -> usize {
+        if let Some(&i) = self.positions.get(&value) {
+            return i + 1;
+        }
+
+        let i = match self.free.pop() {
+            Some(i) => {
+                self.palette[i] = value;
+                i
+            }
+            None => {
+                self.palette.push(value);
+                self.counts.push(0);
+                let needed = bits_for(self.palette.len());
+                if needed > self.bits {
+                    self.repack(needed);
+                }
+                self.palette.len() - 1
+            }
+        };
+        self.positions.insert(value, i);
+        i + 1
+    }
+
+    /// Counts one less use of an entry, freeing it once no slot uses it
+    fn release(&mut self, palette_index: usize) {
+        let i = palette_index - 1;
+        self.counts[i] -= 1;
+        if self.counts[i] == 0 {
+            self.positions.remove(&self.palette[i]);
+            self.free.push(i);
+        }
+    }
+
+    fn write(&mut self, index: usize, palette_index: usize) {
+        assert!(index < self.len, "index {} out of range", index);
+        let per_word = entries_per_word(self.bits);
+        let shift = (index % per_word) as u32 * self.bits;
+        let word = &mut self.words[index / per_word];
+        *word = (*word & !(mask(self.bits) << shift)) | ((palette_index as u64) << shift);
+    }
+
+    fn repack(&mut self, bits: u32) {
+        let mut repacked = PalettedVec::<T> {
+            palette: Vec::new(),
+            positions: HashMap::new(),
+            counts: Vec::new(),
+            free: Vec::new(),
+            bits,
+            len: self.len,
+            words: vec![0; word_count(self.len, bits)],
+        };
+
+        for i in 0..self.len {
+            let palette_index = self.palette_index(i);
+            if palette_index != 0 {
+                repacked.write(i, palette_index);
+            }
+        }
+
+        self.bits = bits;
+        self.words = repacked.words;
+    }
+}
+
+/// Number of bits needed to address `palette_len` values plus the empty index
+fn bits_for(palette_len: usize) -> u32 {
+    (usize::BITS - palette_len.leading_zeros()).max(1)
+}
+
+fn entries_per_word(bits: u32) -> usize {
+    (u64::BITS / bits) as usize
+}
+
+fn word_count(len: usize, bits: u32) -> usize {
+    len.saturating_sub(1) / entries_per_word(bits) + 1
+}
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bits_for, PalettedVec};
+
+    #[test]
+    fn test_bits_for() {
+        assert_eq!(bits_for(0), 1);
+        assert_eq!(bits_for(1), 1);
+        assert_eq!(bits_for(2), 2);
+        assert_eq!(bits_for(3), 2);
+        assert_eq!(bits_for(4), 3);
+        assert_eq!(bits_for(29), 5);
+        assert_eq!(bits_for(255), 8);
+    }
+
+    #[test]
+    fn test_set_get() {
+        let mut vec = PalettedVec::new(1000);
+        for i in 0..1000 {
+            assert_eq!(vec.get(i), None);
+        }
+
+        // Enough distinct values to force several repacks
+        for i in 0..1000 {
+            vec.set(i, Some(i % 37));
+        }
+        for i in 0..1000 {
+            assert_eq!(vec.get(i), Some(i % 37));
+        }
+        assert_eq!(vec.palette().len(), 37);
+
+        vec.set(500, None);
+        assert_eq!(vec.get(500), None);
+        assert_eq!(vec.get(499), Some(499 % 37));
+        assert_eq!(vec.get(501), Some(501 % 37));
+    }
+
+    #[test]
+    fn test_reuse_entries() {
+        let mut vec = PalettedVec::new(100);
+        vec.set(0, Some(1000));
+        // A gradient written over the same slots only ever needs as many entries as it has slots
+        for value in 0..10_000 {
+            vec.set(1 + value % 3, Some(value));
+        }
+        assert_eq!(vec.palette().len(), 5);
+        assert_eq!(vec.bits, 3);
+        assert_eq!(vec.get(0), Some(1000));
+        assert_eq!(vec.get(3), Some(9998));
+
+        for i in 0..4 {
+            vec.set(i, None);
+        }
+        assert!(vec.positions.is_empty());
+        vec.set(99, Some(7));
+        assert_eq!(vec.palette().len(), 5);
+        assert_eq!(vec.get(99), Some(7));
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut vec = PalettedVec::new(128 * 128 * 128);
+        vec.set(0, Some(1u32));
+        assert_eq!(vec.words.len(), 128 * 128 * 128 / 64);
+    }
+}
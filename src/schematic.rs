@@ -1,25 +1,92 @@
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
 use std::io::Write;
 
 use gltf::json::accessor::GenericComponentType;
 use gltf::json::validation::Checked;
 
 use crate::color::Color;
+use crate::palette::PalettedVec;
+
+mod compose;
+mod material;
+mod model;
+mod paint;
+mod shapes;
+mod stats;
+mod transform;
+
+pub use compose::{CsgOp, PasteMode};
+pub use material::Material;
+pub use model::Model;
+pub use stats::Stats;
+pub use transform::Axis;
 
 #[derive(Clone)]
 pub struct Schematic {
     x_size: u8,
     y_size: u8,
     z_size: u8,
-    blocks: Vec<Option<Color>>,
+    blocks: PalettedVec<Voxel>,
+    materials: Vec<Material>,
+}
+
+/// Everything stored for a non-empty voxel
+#[derive(Clone, Copy, Debug)]
+pub struct Voxel {
+    pub color: Color,
+    /// Opacity from 0 (invisible) to 1 (opaque)
+    pub alpha: f32,
+    /// How strongly the voxel glows in its own color. 0 means it doesn't emit light.
+    pub emissive: f32,
+    /// Id of the named material the other fields were copied from
+    pub material: Option<u8>,
+}
+
+// Floats are compared by their bits so that voxels can be palette keys. Every NaN alpha is then
+// one value instead of a new palette entry each time.
+impl PartialEq for Voxel {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Voxel {}
+
+impl Hash for Voxel {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl Voxel {
+    fn key(&self) -> (Color, u32, u32, Option<u8>) {
+        (
+            self.color,
+            self.alpha.to_bits(),
+            self.emissive.to_bits(),
+            self.material,
+        )
+    }
+}
+
+impl From<Color> for Voxel {
+    fn from(color: Color) -> Self {
+        Voxel {
+            color,
+            alpha: 1.,
+            emissive: 0.,
+            material: None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
 #[repr(C)]
 struct Vertex {
     pos: [f32; 3],
-    color: [f32; 3],
+    color: [f32; 4],
 }
 
 impl Schematic {
@@ -30,19 +97,38 @@ impl Schematic {
             x_size,
             y_size,
             z_size,
-            blocks: vec![None; capacity],
+            blocks: PalettedVec::new(capacity),
+            materials: Vec::new(),
+        }
+    }
+
+    /// An empty schematic of the given size with the same materials as this one
+    fn blank(&self, x_size: u8, y_size: u8, z_size: u8) -> Self {
+        Schematic {
+            materials: self.materials.clone(),
+            ..Schematic::new(x_size, y_size, z_size)
         }
     }
 
     pub fn set(&mut self, x: u8, y: u8, z: u8, color: Color) -> Option<()> {
+        self.set_voxel(x, y, z, Voxel::from(color))
+    }
+
+    pub fn set_voxel(&mut self, x: u8, y: u8, z: u8, voxel: Voxel) -> Option<()> {
         let index = self.get_index(x, y, z)?;
-        self.blocks[index] = Some(color);
+        self.blocks.set(index, Some(voxel));
         Some(())
     }
 
-    fn get(&self, x: u8, y: u8, z: u8) -> Option<Option<Color>> {
+    /// Returns `None` if the position is out of bounds, or `Some(None)` if the voxel is empty
+    pub fn get(&self, x: u8, y: u8, z: u8) -> Option<Option<Color>> {
+        Some(self.get_voxel(x, y, z)?.map(|v| v.color))
+    }
+
+    /// Like `get`, but includes transparency and emission
+    pub fn get_voxel(&self, x: u8, y: u8, z: u8) -> Option<Option<Voxel>> {
         let index = self.get_index(x, y, z)?;
-        Some(self.blocks[index])
+        Some(self.blocks.get(index))
     }
 
     pub fn fill(
@@ -55,10 +141,34 @@ impl Schematic {
         z2: u8,
         block: Color,
     ) -> Option<()> {
+        self.fill_voxel([x1, y1, z1], [x2, y2, z2], Voxel::from(block))
+    }
+
+    pub fn fill_voxel(&mut self, from: [u8; 3], to: [u8; 3], voxel: Voxel) -> Option<()> {
+        for x in from[0]..=to[0] {
+            for y in from[1]..=to[1] {
+                for z in from[2]..=to[2] {
+                    self.set_voxel(x, y, z, voxel)?;
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    /// Empties a voxel
+    pub fn erase(&mut self, x: u8, y: u8, z: u8) -> Option<()> {
+        let index = self.get_index(x, y, z)?;
+        self.blocks.set(index, None);
+        Some(())
+    }
+
+    /// Empties every voxel in a region. Like `fill`, both corners are inclusive.
+    pub fn carve(&mut self, x1: u8, y1: u8, z1: u8, x2: u8, y2: u8, z2: u8) -> Option<()> {
         for x in x1..=x2 {
             for y in y1..=y2 {
                 for z in z1..=z2 {
-                    self.set(x, y, z, block)?;
+                    self.erase(x, y, z)?;
                 }
             }
         }
@@ -66,6 +176,17 @@ impl Schematic {
         Some(())
     }
 
+    /// Number of voxels with the given color, or of all non-empty voxels if `color` is `None`
+    pub fn count(&self, color: Option<Color>) -> usize {
+        self.positions()
+            .filter(|&(x, y, z)| match (self.get(x, y, z), color) {
+                (Some(Some(_)), None) => true,
+                (Some(Some(c)), Some(color)) => c == color,
+                _ => false,
+            })
+            .count()
+    }
+
     fn get_index(&self, x: u8, y: u8, z: u8) -> Option<usize> {
         if x >= self.x_size() {
             return None;
@@ -82,6 +203,12 @@ impl Schematic {
         Some((y as usize * self.z_size as usize + z as usize) * self.x_size as usize + x as usize)
     }
 
+    /// Every coordinate in the schematic, whether empty or not
+    fn positions(&self) -> impl Iterator<Item = (u8, u8, u8)> {
+        let (xs, ys, zs) = (self.x_size, self.y_size, self.z_size);
+        (0..xs).flat_map(move |x| (0..ys).flat_map(move |y| (0..zs).map(move |z| (x, y, z))))
+    }
+
     pub fn x_size(&self) -> u8 {
         self.x_size
     }
@@ -95,52 +222,92 @@ impl Schematic {
     }
 
     pub fn serialize<W: Write>(&self, w: &mut W) -> Result<(), Box<dyn std::error::Error>> {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+        let glb = to_glb(|root, buffer| vec![push_node(root, buffer, None, Some(self), vec![])])?;
+        glb.to_writer(w)?;
+        Ok(())
+    }
+
+    /// Builds one mesh for every distinct way voxels have to be shaded
+    fn build_meshes(&self) -> Vec<(Shading, Mesh)> {
+        let palette = self.blocks.palette();
+        let mut meshes: Vec<(Shading, Mesh)> = Vec::new();
+
+        // Work out each palette entry's vertex color and mesh once instead of once per voxel
+        let mut mesh_indices = Vec::with_capacity(palette.len());
+        let mut colors = Vec::with_capacity(palette.len());
+        for voxel in palette {
+            let shading = Shading::of(voxel);
+            let mesh_index = match meshes.iter().position(|(s, _)| *s == shading) {
+                Some(i) => i,
+                None => {
+                    meshes.push((shading, Mesh::default()));
+                    meshes.len() - 1
+                }
+            };
+            mesh_indices.push(mesh_index);
+
+            let [r, g, b] = voxel.color.to_rgb_normalized();
+            colors.push([r, g, b, voxel.alpha]);
+        }
 
         for x in 0..self.x_size() {
             for y in 0..self.y_size() {
                 for z in 0..self.z_size() {
-                    let color = match self.get(x, y, z) {
-                        Some(Some(c)) => c.to_rgb_normalized(),
-                        _ => continue,
+                    let (voxel, color, mesh) =
+                        match self.blocks.palette_index(self.get_index(x, y, z).unwrap()) {
+                            0 => continue,
+                            i => (
+                                palette[i - 1],
+                                colors[i - 1],
+                                &mut meshes[mesh_indices[i - 1]].1,
+                            ),
+                        };
+
+                    let visible = |neighbor: Option<(u8, u8, u8)>| {
+                        match neighbor.and_then(|(x, y, z)| self.get_voxel(x, y, z)) {
+                            // Faces behind translucent voxels can still be seen, except between
+                            // voxels of the same kind so that glass panes look like one surface
+                            Some(Some(n)) => n.alpha < 1. && n != voxel,
+                            _ => true,
+                        }
                     };
 
+                    let (vertices, indices) = (&mut mesh.vertices, &mut mesh.indices);
                     let i = vertices.len() as u32;
                     let mut vertices_added = false;
 
                     // -X Winding order: +Y+Z, +Y-Z, -Y+Z and -Y-Z, -Y+Z, +Y-Z
-                    if x == 0 || self.get(x - 1, y, z).unwrap().is_none() {
+                    if visible(x.checked_sub(1).map(|x| (x, y, z))) {
                         indices.extend_from_slice(&[i + 3, i + 2, i + 1, i, i + 1, i + 2]);
                         vertices_added = true;
                     }
 
                     // +X Winding order: +Y-Z, +Y+Z, -Y+Z and +Y-Z, -Y+Z, -Y-Z
-                    if x == self.x_size() - 1 || self.get(x + 1, y, z).unwrap().is_none() {
+                    if visible(Some((x + 1, y, z))) {
                         indices.extend_from_slice(&[i + 6, i + 7, i + 5, i + 6, i + 5, i + 4]);
                         vertices_added = true;
                     }
 
                     // -Y Winding order: -X-Z, +X-Z, +X+Z and -X-Z, +X+Z, -X+Z
-                    if y == 0 || self.get(x, y - 1, z).unwrap().is_none() {
+                    if visible(y.checked_sub(1).map(|y| (x, y, z))) {
                         indices.extend_from_slice(&[i, i + 4, i + 5, i, i + 5, i + 1]);
                         vertices_added = true;
                     }
 
                     // +Y Winding order: +X+Z, +X-Z, -X-Z and +X+Z, -X-Z, -X+Z
-                    if y == self.y_size() - 1 || self.get(x, y + 1, z).unwrap().is_none() {
+                    if visible(Some((x, y + 1, z))) {
                         indices.extend_from_slice(&[i + 7, i + 6, i + 2, i + 7, i + 2, i + 3]);
                         vertices_added = true;
                     }
 
                     // -Z Winding order: -X+Y, +X+Y, -X-Y and +X+Y, +X-Y, -X-Y
-                    if z == 0 || self.get(x, y, z - 1).unwrap().is_none() {
+                    if visible(z.checked_sub(1).map(|z| (x, y, z))) {
                         indices.extend_from_slice(&[i + 2, i + 6, i, i + 6, i + 4, i]);
                         vertices_added = true;
                     }
 
                     // +Z Winding order: +X+Y, -X+Y, -X-Y and +X+Y, -X-Y, +X-Y
-                    if z == self.z_size() - 1 || self.get(x, y, z + 1).unwrap().is_none() {
+                    if visible(Some((x, y, z + 1))) {
                         indices.extend_from_slice(&[i + 7, i + 3, i + 1, i + 7, i + 1, i + 5]);
                         vertices_added = true;
                     }
@@ -165,17 +332,88 @@ impl Schematic {
             }
         }
 
-        let (optimized_vert, optimized_idx) = remove_unused_vertices(&vertices, &indices);
-        tracing::info!(
-            "Removed {} unused vertices",
-            vertices.len() - optimized_vert.len()
-        );
-        let glb = to_glb(&optimized_vert, &optimized_idx)?;
-        glb.to_writer(w)?;
-        Ok(())
+        let mut removed = 0;
+        for (_, mesh) in &mut meshes {
+            let (vertices, indices) = remove_unused_vertices(&mesh.vertices, &mesh.indices);
+            removed += mesh.vertices.len() - vertices.len();
+            *mesh = Mesh { vertices, indices };
+        }
+        tracing::info!("Removed {} unused vertices", removed);
+
+        meshes.retain(|(_, mesh)| !mesh.indices.is_empty());
+        meshes
+    }
+}
+
+/// How a group of voxels has to be rendered. Every group becomes a separate glTF primitive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Shading {
+    Opaque,
+    Translucent,
+    /// glTF has no per-vertex emission, so every emissive voxel kind needs its own material
+    Emissive(Voxel),
+    /// Voxels tagged with a named material
+    Material(u8),
+}
+
+impl Shading {
+    fn of(voxel: &Voxel) -> Shading {
+        if let Some(id) = voxel.material {
+            Shading::Material(id)
+        } else if voxel.emissive > 0. {
+            Shading::Emissive(*voxel)
+        } else if voxel.alpha < 1. {
+            Shading::Translucent
+        } else {
+            Shading::Opaque
+        }
+    }
+
+    /// Returns `None` for the glTF default material
+    fn material(self, materials: &[Material]) -> Option<gltf::json::Material> {
+        match self {
+            Shading::Opaque => None,
+            Shading::Translucent => Some(translucent()),
+            Shading::Emissive(voxel) => Some(surface(voxel.color, voxel.alpha, voxel.emissive)),
+            Shading::Material(id) => materials.get(id as usize).map(Material::to_gltf),
+        }
+    }
+}
+
+/// Material that blends by the alpha of the vertex colors and otherwise keeps the glTF defaults
+fn translucent() -> gltf::json::Material {
+    gltf::json::Material {
+        alpha_mode: Checked::Valid(gltf::json::material::AlphaMode::Blend),
+        ..Default::default()
     }
 }
 
+/// Material with blending enabled if `alpha` is below 1 and emission if `emissive` is above 0
+fn surface(color: Color, alpha: f32, emissive: f32) -> gltf::json::Material {
+    let mut material = if alpha < 1. {
+        translucent()
+    } else {
+        gltf::json::Material::default()
+    };
+    if emissive > 0. {
+        material.emissive_factor = gltf::json::material::EmissiveFactor(color.to_rgb_normalized());
+        material.extensions = Some(gltf::json::extensions::material::Material {
+            emissive_strength: Some(gltf::json::extensions::material::EmissiveStrength {
+                emissive_strength: gltf::json::extensions::material::EmissiveStrengthFactor(
+                    emissive,
+                ),
+            }),
+        });
+    }
+    material
+}
+
+#[derive(Default)]
+struct Mesh {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
 fn remove_unused_vertices(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
     let mut index_convert = HashMap::with_capacity(vertices.len());
     let mut new_vertices = Vec::with_capacity(vertices.len());
@@ -195,154 +433,41 @@ fn remove_unused_vertices(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>,
     (new_vertices, new_indices)
 }
 
-fn to_glb<'a>(
-    vertices: &[Vertex],
-    indices: &[u32],
-) -> Result<gltf::binary::Glb<'a>, gltf::json::Error> {
-    let vertices_bytes = bytemuck::cast_slice(&vertices);
-    let indices_bytes = bytemuck::cast_slice(&indices);
-    let buffer = [vertices_bytes, indices_bytes].concat();
-
-    let mut min = [f32::MAX, f32::MAX, f32::MAX];
-    let mut max = [f32::MIN, f32::MIN, f32::MIN];
-
-    for vertex in vertices {
-        for i in 0..3 {
-            min[i] = f32::min(min[i], vertex.pos[i]);
-            max[i] = f32::max(max[i], vertex.pos[i]);
-        }
+/// Writes a glTF scene whose root nodes are returned by `push_nodes`
+fn to_glb<'a, F>(push_nodes: F) -> Result<gltf::binary::Glb<'a>, gltf::json::Error>
+where
+    F: FnOnce(&mut gltf::json::Root, &mut Vec<u8>) -> Vec<gltf::json::Index<gltf::json::Node>>,
+{
+    let mut root = gltf::json::Root::default();
+    let mut buffer = Vec::new();
+
+    let nodes = push_nodes(&mut root, &mut buffer);
+
+    if root.materials.iter().any(|m| {
+        m.extensions
+            .as_ref()
+            .and_then(|e| e.emissive_strength.as_ref())
+            .is_some()
+    }) {
+        root.extensions_used
+            .push("KHR_materials_emissive_strength".to_owned());
     }
 
-    let json = gltf::json::serialize::to_string(&gltf::json::Root {
-        accessors: vec![
-            gltf::json::Accessor {
-                buffer_view: Some(gltf::json::Index::new(0)),
-                byte_offset: Some(0),
-                count: vertices.len() as u32,
-                component_type: Checked::Valid(GenericComponentType(
-                    gltf::json::accessor::ComponentType::F32,
-                )),
-                extensions: Default::default(),
-                extras: Default::default(),
-                type_: Checked::Valid(gltf::json::accessor::Type::Vec3),
-                min: Some(Vec::from(min).into()),
-                max: Some(Vec::from(max).into()),
-                name: None,
-                normalized: false,
-                sparse: None,
-            },
-            gltf::json::Accessor {
-                buffer_view: Some(gltf::json::Index::new(0)),
-                byte_offset: Some((3 * std::mem::size_of::<f32>()) as u32),
-                count: vertices.len() as u32,
-                component_type: Checked::Valid(GenericComponentType(
-                    gltf::json::accessor::ComponentType::F32,
-                )),
-                extensions: Default::default(),
-                extras: Default::default(),
-                type_: Checked::Valid(gltf::json::accessor::Type::Vec3),
-                min: None,
-                max: None,
-                name: None,
-                normalized: false,
-                sparse: None,
-            },
-            gltf::json::Accessor {
-                buffer_view: Some(gltf::json::Index::new(1)),
-                byte_offset: Some(0),
-                count: indices.len() as u32,
-                component_type: Checked::Valid(GenericComponentType(
-                    gltf::json::accessor::ComponentType::U32,
-                )),
-                extensions: Default::default(),
-                extras: Default::default(),
-                type_: Checked::Valid(gltf::json::accessor::Type::Scalar),
-                min: None,
-                max: None,
-                name: None,
-                normalized: false,
-                sparse: None,
-            },
-        ],
-        buffers: vec![gltf::json::Buffer {
-            byte_length: buffer.len() as u32,
-            extensions: Default::default(),
-            extras: Default::default(),
-            name: None,
-            uri: None,
-        }],
-        buffer_views: vec![
-            gltf::json::buffer::View {
-                buffer: gltf::json::Index::new(0),
-                byte_length: vertices_bytes.len() as u32,
-                byte_offset: None,
-                byte_stride: Some(std::mem::size_of::<Vertex>() as u32),
-                extensions: Default::default(),
-                extras: Default::default(),
-                name: None,
-                target: Some(Checked::Valid(gltf::json::buffer::Target::ArrayBuffer)),
-            },
-            gltf::json::buffer::View {
-                buffer: gltf::json::Index::new(0),
-                byte_length: indices_bytes.len() as u32,
-                byte_offset: Some(vertices_bytes.len() as u32),
-                byte_stride: None,
-                extensions: Default::default(),
-                extras: Default::default(),
-                name: None,
-                target: Some(Checked::Valid(
-                    gltf::json::buffer::Target::ElementArrayBuffer,
-                )),
-            },
-        ],
-        meshes: vec![gltf::json::Mesh {
-            extensions: Default::default(),
-            extras: Default::default(),
-            name: None,
-            primitives: vec![gltf::json::mesh::Primitive {
-                attributes: {
-                    let mut map = BTreeMap::new();
-                    map.insert(
-                        Checked::Valid(gltf::json::mesh::Semantic::Positions),
-                        gltf::json::Index::new(0),
-                    );
-                    map.insert(
-                        Checked::Valid(gltf::json::mesh::Semantic::Colors(0)),
-                        gltf::json::Index::new(1),
-                    );
-                    map
-                },
-                extensions: Default::default(),
-                extras: Default::default(),
-                indices: Some(gltf::json::Index::new(2)),
-                material: None,
-                mode: Checked::Valid(gltf::json::mesh::Mode::Triangles),
-                targets: None,
-            }],
-            weights: None,
-        }],
-        nodes: vec![gltf::json::Node {
-            camera: None,
-            children: None,
-            extras: Default::default(),
-            extensions: Default::default(),
-            matrix: None,
-            mesh: Some(gltf::json::Index::new(0)),
-            name: None,
-            rotation: None,
-            scale: None,
-            translation: None,
-            skin: None,
-            weights: None,
-        }],
-        scenes: vec![gltf::json::Scene {
-            extensions: Default::default(),
-            extras: Default::default(),
-            name: None,
-            nodes: vec![gltf::json::Index::new(0)],
-        }],
-        ..Default::default()
-    })?;
+    root.buffers = vec![gltf::json::Buffer {
+        byte_length: buffer.len() as u32,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        uri: None,
+    }];
+    root.scenes = vec![gltf::json::Scene {
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        nodes,
+    }];
+
+    let json = gltf::json::serialize::to_string(&root)?;
 
     Ok(gltf::binary::Glb {
         header: gltf::binary::Header {
@@ -355,11 +480,192 @@ fn to_glb<'a>(
     })
 }
 
+/// Appends a node with the given name and children to the scene and returns its index. The node
+/// has a mesh if `schematic` has any visible voxels.
+fn push_node(
+    root: &mut gltf::json::Root,
+    buffer: &mut Vec<u8>,
+    name: Option<&str>,
+    schematic: Option<&Schematic>,
+    children: Vec<gltf::json::Index<gltf::json::Node>>,
+) -> gltf::json::Index<gltf::json::Node> {
+    let primitives: Vec<_> = schematic
+        .map(|schematic| {
+            schematic
+                .build_meshes()
+                .iter()
+                .map(|(shading, mesh)| {
+                    let material = shading.material(&schematic.materials);
+                    push_primitive(root, buffer, mesh, material)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // glTF meshes need at least one primitive
+    let mesh = (!primitives.is_empty()).then(|| {
+        root.meshes.push(gltf::json::Mesh {
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: name.map(str::to_owned),
+            primitives,
+            weights: None,
+        });
+        gltf::json::Index::new(root.meshes.len() as u32 - 1)
+    });
+
+    root.nodes.push(gltf::json::Node {
+        camera: None,
+        children: (!children.is_empty()).then_some(children),
+        extras: Default::default(),
+        extensions: Default::default(),
+        matrix: None,
+        mesh,
+        name: name.map(str::to_owned),
+        rotation: None,
+        scale: None,
+        translation: None,
+        skin: None,
+        weights: None,
+    });
+    gltf::json::Index::new(root.nodes.len() as u32 - 1)
+}
+
+/// Appends a mesh's vertices and indices to `buffer` along with the buffer views, accessors and
+/// material needed to reference them. A `material` of `None` uses the glTF default material.
+fn push_primitive(
+    root: &mut gltf::json::Root,
+    buffer: &mut Vec<u8>,
+    mesh: &Mesh,
+    material: Option<gltf::json::Material>,
+) -> gltf::json::mesh::Primitive {
+    let vertices_bytes: &[u8] = bytemuck::cast_slice(&mesh.vertices);
+    let indices_bytes: &[u8] = bytemuck::cast_slice(&mesh.indices);
+
+    let mut min = [f32::MAX, f32::MAX, f32::MAX];
+    let mut max = [f32::MIN, f32::MIN, f32::MIN];
+
+    for vertex in &mesh.vertices {
+        for i in 0..3 {
+            min[i] = f32::min(min[i], vertex.pos[i]);
+            max[i] = f32::max(max[i], vertex.pos[i]);
+        }
+    }
+
+    let vertex_view = gltf::json::Index::new(root.buffer_views.len() as u32);
+    root.buffer_views.push(gltf::json::buffer::View {
+        buffer: gltf::json::Index::new(0),
+        byte_length: vertices_bytes.len() as u32,
+        byte_offset: Some(buffer.len() as u32),
+        byte_stride: Some(std::mem::size_of::<Vertex>() as u32),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: Some(Checked::Valid(gltf::json::buffer::Target::ArrayBuffer)),
+    });
+    buffer.extend_from_slice(vertices_bytes);
+
+    let index_view = gltf::json::Index::new(root.buffer_views.len() as u32);
+    root.buffer_views.push(gltf::json::buffer::View {
+        buffer: gltf::json::Index::new(0),
+        byte_length: indices_bytes.len() as u32,
+        byte_offset: Some(buffer.len() as u32),
+        byte_stride: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: Some(Checked::Valid(
+            gltf::json::buffer::Target::ElementArrayBuffer,
+        )),
+    });
+    buffer.extend_from_slice(indices_bytes);
+
+    let positions = gltf::json::Index::new(root.accessors.len() as u32);
+    root.accessors.push(gltf::json::Accessor {
+        buffer_view: Some(vertex_view),
+        byte_offset: Some(0),
+        count: mesh.vertices.len() as u32,
+        component_type: Checked::Valid(GenericComponentType(
+            gltf::json::accessor::ComponentType::F32,
+        )),
+        extensions: Default::default(),
+        extras: Default::default(),
+        type_: Checked::Valid(gltf::json::accessor::Type::Vec3),
+        min: Some(Vec::from(min).into()),
+        max: Some(Vec::from(max).into()),
+        name: None,
+        normalized: false,
+        sparse: None,
+    });
+
+    let colors = gltf::json::Index::new(root.accessors.len() as u32);
+    root.accessors.push(gltf::json::Accessor {
+        buffer_view: Some(vertex_view),
+        byte_offset: Some((3 * std::mem::size_of::<f32>()) as u32),
+        count: mesh.vertices.len() as u32,
+        component_type: Checked::Valid(GenericComponentType(
+            gltf::json::accessor::ComponentType::F32,
+        )),
+        extensions: Default::default(),
+        extras: Default::default(),
+        type_: Checked::Valid(gltf::json::accessor::Type::Vec4),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    });
+
+    let indices = gltf::json::Index::new(root.accessors.len() as u32);
+    root.accessors.push(gltf::json::Accessor {
+        buffer_view: Some(index_view),
+        byte_offset: Some(0),
+        count: mesh.indices.len() as u32,
+        component_type: Checked::Valid(GenericComponentType(
+            gltf::json::accessor::ComponentType::U32,
+        )),
+        extensions: Default::default(),
+        extras: Default::default(),
+        type_: Checked::Valid(gltf::json::accessor::Type::Scalar),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    });
+
+    let material = material.map(|material| {
+        root.materials.push(material);
+        gltf::json::Index::new(root.materials.len() as u32 - 1)
+    });
+
+    gltf::json::mesh::Primitive {
+        attributes: {
+            let mut map = BTreeMap::new();
+            map.insert(
+                Checked::Valid(gltf::json::mesh::Semantic::Positions),
+                positions,
+            );
+            map.insert(
+                Checked::Valid(gltf::json::mesh::Semantic::Colors(0)),
+                colors,
+            );
+            map
+        },
+        extensions: Default::default(),
+        extras: Default::default(),
+        indices: Some(indices),
+        material,
+        mode: Checked::Valid(gltf::json::mesh::Mode::Triangles),
+        targets: None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::color::Color;
 
-    use super::{remove_unused_vertices, Schematic, Vertex};
+    use super::{remove_unused_vertices, Schematic, Shading, Vertex, Voxel};
 
     #[test]
     fn test_coordinates() {
@@ -402,12 +708,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_erase_carve() {
+        let mut schem = Schematic::new(10, 10, 10);
+        schem.fill(0, 0, 0, 9, 9, 9, Color(1, 2, 3)).unwrap();
+        schem.erase(0, 0, 0).unwrap();
+        schem.carve(2, 2, 2, 7, 7, 7).unwrap();
+        assert!(schem.erase(10, 0, 0).is_none());
+
+        for x in 0..schem.x_size() {
+            for y in 0..schem.y_size() {
+                for z in 0..schem.z_size() {
+                    let carved =
+                        (2..=7).contains(&x) && (2..=7).contains(&y) && (2..=7).contains(&z);
+                    let expected = if carved || (x, y, z) == (0, 0, 0) {
+                        None
+                    } else {
+                        Some(Color(1, 2, 3))
+                    };
+
+                    assert_eq!(schem.get(x, y, z).unwrap(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_materials() {
+        let glass = Voxel {
+            color: Color(200, 230, 255),
+            alpha: 0.5,
+            emissive: 0.,
+            material: None,
+        };
+        let lantern = Voxel {
+            color: Color(255, 200, 0),
+            alpha: 1.,
+            emissive: 2.,
+            material: None,
+        };
+
+        let mut schem = Schematic::new(4, 1, 1);
+        schem.set(0, 0, 0, Color(255, 0, 0)).unwrap();
+        schem.set_voxel(1, 0, 0, glass).unwrap();
+        schem.set_voxel(2, 0, 0, glass).unwrap();
+        schem.set_voxel(3, 0, 0, lantern).unwrap();
+        assert_eq!(schem.get(1, 0, 0).unwrap(), Some(glass.color));
+
+        let meshes = schem.build_meshes();
+        let shadings: Vec<Shading> = meshes.iter().map(|(s, _)| *s).collect();
+        assert_eq!(
+            shadings,
+            [
+                Shading::Opaque,
+                Shading::Translucent,
+                Shading::Emissive(lantern)
+            ]
+        );
+        // The opaque face behind the glass is kept, but not the faces between the two glass voxels
+        // or those covered by opaque neighbors
+        assert_eq!(meshes[0].1.indices.len(), 6 * 6);
+        assert_eq!(meshes[1].1.indices.len(), 6 * 8);
+
+        let mut glb = Vec::new();
+        schem.serialize(&mut glb).unwrap();
+        let gltf = gltf::Gltf::from_slice(&glb).unwrap();
+        assert_eq!(gltf.meshes().next().unwrap().primitives().count(), 3);
+        assert_eq!(gltf.materials().count(), 2);
+        assert!(gltf
+            .extensions_used()
+            .any(|e| e == "KHR_materials_emissive_strength"));
+    }
+
     #[test]
     fn test_remove_unused_vertices() {
         fn vert(n: f32) -> Vertex {
             Vertex {
                 pos: [n; 3],
-                color: [n; 3],
+                color: [n; 4],
             }
         }
 
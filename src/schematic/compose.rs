@@ -0,0 +1,216 @@
+use strum_macros::EnumString;
+
+use super::Schematic;
+
+/// How voxels from a pasted schematic are merged into the destination
+#[derive(Clone, Copy, Debug, EnumString, PartialEq)]
+pub enum PasteMode {
+    /// Non-empty voxels replace whatever is in the destination
+    #[strum(serialize = "overwrite")]
+    Overwrite,
+    /// Non-empty voxels are only placed where the destination is empty
+    #[strum(serialize = "keep-existing")]
+    KeepExisting,
+    /// The whole region is replaced, so empty voxels clear the destination too
+    #[strum(serialize = "replace-air")]
+    ReplaceAir,
+}
+
+/// Boolean operation between two schematics. Where both schematics have a voxel, the color of the
+/// destination is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Difference,
+    Intersection,
+    Xor,
+}
+
+impl Schematic {
+    /// Copies the inclusive region between two corners into a new schematic
+    pub fn copy(&self, from: [u8; 3], to: [u8; 3]) -> Option<Schematic> {
+        let min = [0, 1, 2].map(|i| from[i].min(to[i]));
+        let max = [0, 1, 2].map(|i| from[i].max(to[i]));
+        self.get_index(max[0], max[1], max[2])?;
+
+        let size = [0, 1, 2].map(|i| max[i] - min[i] + 1);
+        let mut copy = self.blank(size[0], size[1], size[2]);
+        for (x, y, z) in copy.positions() {
+            if let Some(Some(voxel)) = self.get_voxel(x + min[0], y + min[1], z + min[2]) {
+                copy.set_voxel(x, y, z, voxel)?;
+            }
+        }
+        Some(copy)
+    }
+
+    /// Pastes `src` with its origin at `at`. Nothing is changed if any part of `src` would be out
+    /// of bounds.
+    pub fn paste(&mut self, src: &Schematic, at: [u8; 3], mode: PasteMode) -> Option<()> {
+        if !self.fits(src, at) {
+            return None;
+        }
+        let import = self.import_materials(src)?;
+
+        for (x, y, z) in src.positions() {
+            let index = self.get_index(x + at[0], y + at[1], z + at[2])?;
+            let value = src.get_voxel(x, y, z)?.map(&import);
+            let replace = match mode {
+                PasteMode::Overwrite => value.is_some(),
+                PasteMode::KeepExisting => value.is_some() && self.blocks.get(index).is_none(),
+                PasteMode::ReplaceAir => true,
+            };
+
+            if replace {
+                self.blocks.set(index, value);
+            }
+        }
+        Some(())
+    }
+
+    /// Combines `other`, placed with its origin at `at`, into this schematic. `other` must fit
+    /// entirely within this schematic. Anything outside of `other` counts as empty, so an
+    /// intersection also clears every voxel outside of it.
+    pub fn csg(&mut self, other: &Schematic, at: [u8; 3], op: CsgOp) -> Option<()> {
+        if !self.fits(other, at) {
+            return None;
+        }
+        let import = self.import_materials(other)?;
+
+        for (x, y, z) in self.positions() {
+            let other_voxel = match (
+                x.checked_sub(at[0]),
+                y.checked_sub(at[1]),
+                z.checked_sub(at[2]),
+            ) {
+                (Some(ox), Some(oy), Some(oz)) => {
+                    other.get_voxel(ox, oy, oz).flatten().map(&import)
+                }
+                _ => None,
+            };
+            let index = self.get_index(x, y, z)?;
+            let voxel = self.blocks.get(index);
+
+            let result = match op {
+                CsgOp::Union => voxel.or(other_voxel),
+                CsgOp::Difference => voxel.filter(|_| other_voxel.is_none()),
+                CsgOp::Intersection => voxel.filter(|_| other_voxel.is_some()),
+                CsgOp::Xor => voxel.xor(other_voxel),
+            };
+
+            if result != voxel {
+                self.blocks.set(index, result);
+            }
+        }
+        Some(())
+    }
+
+    /// Whether `other` fits entirely within this schematic when its origin is placed at `at`
+    fn fits(&self, other: &Schematic, at: [u8; 3]) -> bool {
+        at[0] as usize + other.x_size as usize <= self.x_size as usize
+            && at[1] as usize + other.y_size as usize <= self.y_size as usize
+            && at[2] as usize + other.z_size as usize <= self.z_size as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::color::Color;
+    use crate::schematic::Schematic;
+
+    use super::{CsgOp, PasteMode};
+
+    const RED: Color = Color(255, 0, 0);
+    const BLUE: Color = Color(0, 0, 255);
+
+    #[test]
+    fn test_paste_mode_from_str() {
+        assert_eq!(PasteMode::from_str("overwrite"), Ok(PasteMode::Overwrite));
+        assert_eq!(
+            PasteMode::from_str("keep-existing"),
+            Ok(PasteMode::KeepExisting)
+        );
+        assert_eq!(
+            PasteMode::from_str("replace-air"),
+            Ok(PasteMode::ReplaceAir)
+        );
+        assert!(PasteMode::from_str("merge").is_err());
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut schem = Schematic::new(10, 10, 10);
+        schem.set(2, 3, 4, RED).unwrap();
+        schem.set(9, 9, 9, BLUE).unwrap();
+
+        let copy = schem.copy([4, 5, 6], [2, 3, 4]).unwrap();
+        assert_eq!((copy.x_size(), copy.y_size(), copy.z_size()), (3, 3, 3));
+        assert_eq!(copy.get(0, 0, 0).unwrap(), Some(RED));
+        assert_eq!(copy.bounding_box(), Some(([0, 0, 0], [0, 0, 0])));
+
+        assert!(schem.copy([0, 0, 0], [10, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_paste() {
+        let mut src = Schematic::new(2, 1, 1);
+        src.set(0, 0, 0, RED).unwrap();
+
+        let mut dest = Schematic::new(4, 4, 4);
+        dest.fill(0, 0, 0, 3, 0, 0, BLUE).unwrap();
+
+        let mut overwrite = dest.clone();
+        overwrite
+            .paste(&src, [0, 0, 0], PasteMode::Overwrite)
+            .unwrap();
+        assert_eq!(overwrite.get(0, 0, 0).unwrap(), Some(RED));
+        assert_eq!(overwrite.get(1, 0, 0).unwrap(), Some(BLUE));
+
+        let mut keep = dest.clone();
+        keep.paste(&src, [0, 0, 0], PasteMode::KeepExisting)
+            .unwrap();
+        keep.paste(&src, [0, 1, 0], PasteMode::KeepExisting)
+            .unwrap();
+        assert_eq!(keep.get(0, 0, 0).unwrap(), Some(BLUE));
+        assert_eq!(keep.get(0, 1, 0).unwrap(), Some(RED));
+
+        let mut replace = dest.clone();
+        replace
+            .paste(&src, [0, 0, 0], PasteMode::ReplaceAir)
+            .unwrap();
+        assert_eq!(replace.get(0, 0, 0).unwrap(), Some(RED));
+        assert_eq!(replace.get(1, 0, 0).unwrap(), None);
+        assert_eq!(replace.get(2, 0, 0).unwrap(), Some(BLUE));
+
+        assert!(dest.paste(&src, [3, 0, 0], PasteMode::Overwrite).is_none());
+        assert_eq!(dest.get(3, 0, 0).unwrap(), Some(BLUE));
+    }
+
+    #[test]
+    fn test_csg() {
+        // One voxel each in the destination only, the other schematic only and both
+        let mut dest = Schematic::new(3, 1, 1);
+        dest.set(0, 0, 0, RED).unwrap();
+        dest.set(1, 0, 0, RED).unwrap();
+        let mut other = Schematic::new(2, 1, 1);
+        other.set(0, 0, 0, BLUE).unwrap();
+        other.set(1, 0, 0, BLUE).unwrap();
+
+        let results = [
+            (CsgOp::Union, [Some(RED), Some(RED), Some(BLUE)]),
+            (CsgOp::Difference, [Some(RED), None, None]),
+            (CsgOp::Intersection, [None, Some(RED), None]),
+            (CsgOp::Xor, [Some(RED), None, Some(BLUE)]),
+        ];
+        for (op, expected) in results {
+            let mut schem = dest.clone();
+            schem.csg(&other, [1, 0, 0], op).unwrap();
+            for (x, voxel) in expected.into_iter().enumerate() {
+                assert_eq!(schem.get(x as u8, 0, 0).unwrap(), voxel, "{:?}", op);
+            }
+        }
+
+        assert!(dest.csg(&other, [2, 0, 0], CsgOp::Union).is_none());
+    }
+}
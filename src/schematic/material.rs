@@ -0,0 +1,190 @@
+use gltf::json::validation::Checked;
+
+use crate::color::Color;
+
+use super::{Schematic, Voxel};
+
+/// A named physically based material. Voxels tagged with it share one glTF material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub color: Color,
+    /// Opacity from 0 (invisible) to 1 (opaque)
+    pub alpha: f32,
+    pub emissive: f32,
+    /// From 0 (mirror-like) to 1 (completely diffuse)
+    pub roughness: f32,
+    /// From 0 (dielectric, like wood or stone) to 1 (metal)
+    pub metallic: f32,
+}
+
+impl Material {
+    /// A voxel tagged with this material, which is stored under `id`
+    fn voxel(&self, id: u8) -> Voxel {
+        Voxel {
+            color: self.color,
+            alpha: self.alpha,
+            emissive: self.emissive,
+            material: Some(id),
+        }
+    }
+
+    pub(super) fn to_gltf(&self) -> gltf::json::Material {
+        let mut material = super::surface(self.color, self.alpha, self.emissive);
+        material.name = Some(self.name.clone());
+        material.pbr_metallic_roughness = gltf::json::material::PbrMetallicRoughness {
+            metallic_factor: gltf::json::material::StrengthFactor(self.metallic),
+            roughness_factor: gltf::json::material::StrengthFactor(self.roughness),
+            ..Default::default()
+        };
+        if material.alpha_mode == Checked::Valid(gltf::json::material::AlphaMode::Blend) {
+            // Glass and water are usually seen from both sides
+            material.double_sided = true;
+        }
+        material
+    }
+}
+
+impl Schematic {
+    /// Adds a material to the palette and returns its id. Returns `None` if a material with the
+    /// same name already exists or the palette is full.
+    pub fn define_material(&mut self, material: Material) -> Option<u8> {
+        if self.material_id(&material.name).is_some() {
+            return None;
+        }
+
+        let id = u8::try_from(self.materials.len()).ok()?;
+        self.materials.push(material);
+        Some(id)
+    }
+
+    /// A voxel tagged with the named material, if it has been defined
+    pub fn material_voxel(&self, name: &str) -> Option<Voxel> {
+        let id = self.material_id(name)?;
+        Some(self.materials[id as usize].voxel(id))
+    }
+
+    fn material_id(&self, name: &str) -> Option<u8> {
+        self.materials
+            .iter()
+            .position(|m| m.name == name)
+            .map(|i| i as u8)
+    }
+
+    /// Makes every material of `other` available in this schematic and returns a function that
+    /// converts voxels of `other` into voxels of this schematic. Materials are matched by name and
+    /// the existing definition wins. Returns `None` if the palette would overflow.
+    pub(super) fn import_materials(
+        &mut self,
+        other: &Schematic,
+    ) -> Option<impl Fn(Voxel) -> Voxel> {
+        let mut imported = Vec::with_capacity(other.materials.len());
+        for material in &other.materials {
+            let voxel = match self.material_voxel(&material.name) {
+                Some(voxel) => voxel,
+                None => {
+                    let id = self.define_material(material.clone())?;
+                    material.voxel(id)
+                }
+            };
+            imported.push(voxel);
+        }
+
+        Some(move |voxel: Voxel| match voxel.material {
+            Some(id) => imported[id as usize],
+            None => voxel,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::schematic::{PasteMode, Schematic};
+
+    use super::Material;
+
+    fn material(name: &str, color: Color) -> Material {
+        Material {
+            name: name.to_owned(),
+            color,
+            alpha: 1.,
+            emissive: 0.,
+            roughness: 0.5,
+            metallic: 0.,
+        }
+    }
+
+    #[test]
+    fn test_define_material() {
+        let mut schem = Schematic::new(2, 2, 2);
+        assert_eq!(
+            schem.define_material(material("wood", Color(130, 90, 50))),
+            Some(0)
+        );
+        assert_eq!(
+            schem.define_material(material("metal", Color(200, 200, 200))),
+            Some(1)
+        );
+        assert_eq!(
+            schem.define_material(material("wood", Color(0, 0, 0))),
+            None
+        );
+
+        let metal = schem.material_voxel("metal").unwrap();
+        assert_eq!(metal.material, Some(1));
+        schem.set_voxel(0, 0, 0, metal).unwrap();
+        assert_eq!(schem.get(0, 0, 0).unwrap(), Some(Color(200, 200, 200)));
+        assert!(schem.material_voxel("glass").is_none());
+    }
+
+    #[test]
+    fn test_import_materials() {
+        let mut src = Schematic::new(1, 1, 1);
+        src.define_material(material("glass", Color(200, 230, 255)))
+            .unwrap();
+        src.define_material(material("wood", Color(130, 90, 50)))
+            .unwrap();
+        src.set_voxel(0, 0, 0, src.material_voxel("wood").unwrap())
+            .unwrap();
+
+        let mut dest = Schematic::new(2, 1, 1);
+        dest.define_material(material("wood", Color(100, 70, 40)))
+            .unwrap();
+        dest.paste(&src, [1, 0, 0], PasteMode::Overwrite).unwrap();
+
+        // The destination's own wood is used and glass is added after it
+        assert_eq!(dest.get_voxel(1, 0, 0).unwrap().unwrap().material, Some(0));
+        assert_eq!(dest.get(1, 0, 0).unwrap(), Some(Color(100, 70, 40)));
+        assert_eq!(dest.material_voxel("glass").unwrap().material, Some(1));
+    }
+
+    #[test]
+    fn test_gltf_material() {
+        let mut schem = Schematic::new(2, 1, 1);
+        schem
+            .define_material(Material {
+                metallic: 1.,
+                roughness: 0.25,
+                ..material("steel", Color(180, 180, 190))
+            })
+            .unwrap();
+        schem
+            .set_voxel(0, 0, 0, schem.material_voxel("steel").unwrap())
+            .unwrap();
+        schem.set(1, 0, 0, Color(255, 0, 0)).unwrap();
+
+        let mut glb = Vec::new();
+        schem.serialize(&mut glb).unwrap();
+        let gltf = gltf::Gltf::from_slice(&glb).unwrap();
+        let materials: Vec<_> = gltf.materials().collect();
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials[0].name(), Some("steel"));
+        assert_eq!(materials[0].pbr_metallic_roughness().metallic_factor(), 1.);
+        assert_eq!(
+            materials[0].pbr_metallic_roughness().roughness_factor(),
+            0.25
+        );
+        assert_eq!(gltf.meshes().next().unwrap().primitives().count(), 2);
+    }
+}
@@ -0,0 +1,259 @@
+use std::io::Write;
+
+use gltf::json::accessor::GenericComponentType;
+use gltf::json::validation::Checked;
+
+use super::{push_node, to_glb, Schematic};
+
+/// Output of a build. Every named part becomes its own glTF node so it can be animated, hidden or
+/// shown separately.
+#[derive(Clone)]
+pub enum Model {
+    Schematic(Schematic),
+    /// Named parts in output order. Parts share the same coordinate system, so a door and its frame
+    /// line up when they are built in schematics of the same size.
+    Parts(Vec<(String, Model)>),
+    /// Frames shown one after another in a loop. Every frame is a separate node and only one of
+    /// them is visible at a time.
+    Frames {
+        frames: Vec<Model>,
+        seconds_per_frame: f32,
+    },
+}
+
+impl Model {
+    pub fn serialize<W: Write>(&self, w: &mut W) -> Result<(), Box<dyn std::error::Error>> {
+        if let Model::Schematic(schematic) = self {
+            return schematic.serialize(w);
+        }
+
+        let glb = to_glb(|root, buffer| match self {
+            Model::Parts(parts) => parts
+                .iter()
+                .map(|(name, part)| part.push_node(root, buffer, name))
+                .collect(),
+            _ => vec![self.push_node(root, buffer, "animation")],
+        })?;
+        glb.to_writer(w)?;
+        Ok(())
+    }
+
+    fn push_node(
+        &self,
+        root: &mut gltf::json::Root,
+        buffer: &mut Vec<u8>,
+        name: &str,
+    ) -> gltf::json::Index<gltf::json::Node> {
+        match self {
+            Model::Schematic(schematic) => {
+                push_node(root, buffer, Some(name), Some(schematic), vec![])
+            }
+            Model::Parts(parts) => {
+                let children = parts
+                    .iter()
+                    .map(|(name, part)| part.push_node(root, buffer, name))
+                    .collect();
+                push_node(root, buffer, Some(name), None, children)
+            }
+            Model::Frames {
+                frames,
+                seconds_per_frame,
+            } => {
+                let children: Vec<_> = frames
+                    .iter()
+                    .enumerate()
+                    .map(|(i, frame)| frame.push_node(root, buffer, &format!("{}_{}", name, i)))
+                    .collect();
+
+                // Viewers that don't play animations show the first frame
+                for child in children.iter().skip(1) {
+                    root.nodes[child.value()].scale = Some([0.; 3]);
+                }
+                push_visibility_animation(root, buffer, name, &children, *seconds_per_frame);
+
+                push_node(root, buffer, Some(name), None, children)
+            }
+        }
+    }
+}
+
+/// Adds an animation that shows one node after the other. glTF can't animate visibility, so hidden
+/// nodes are scaled to 0 instead.
+fn push_visibility_animation(
+    root: &mut gltf::json::Root,
+    buffer: &mut Vec<u8>,
+    name: &str,
+    nodes: &[gltf::json::Index<gltf::json::Node>],
+    seconds_per_frame: f32,
+) {
+    // The extra keyframe at the end shows the last frame for its full duration before looping
+    let times: Vec<f32> = (0..=nodes.len())
+        .map(|i| i as f32 * seconds_per_frame)
+        .collect();
+    let input = push_accessor(root, buffer, &times, gltf::json::accessor::Type::Scalar);
+
+    let mut animation = gltf::json::Animation {
+        extensions: Default::default(),
+        extras: Default::default(),
+        channels: Vec::with_capacity(nodes.len()),
+        name: Some(name.to_owned()),
+        samplers: Vec::with_capacity(nodes.len()),
+    };
+    for (i, node) in nodes.iter().enumerate() {
+        let scales: Vec<f32> = (0..=nodes.len())
+            .flat_map(|key| [if key % nodes.len() == i { 1. } else { 0. }; 3])
+            .collect();
+        let output = push_accessor(root, buffer, &scales, gltf::json::accessor::Type::Vec3);
+
+        animation.channels.push(gltf::json::animation::Channel {
+            sampler: gltf::json::Index::new(animation.samplers.len() as u32),
+            target: gltf::json::animation::Target {
+                extensions: Default::default(),
+                extras: Default::default(),
+                node: *node,
+                path: Checked::Valid(gltf::json::animation::Property::Scale),
+            },
+            extensions: Default::default(),
+            extras: Default::default(),
+        });
+        animation.samplers.push(gltf::json::animation::Sampler {
+            extensions: Default::default(),
+            extras: Default::default(),
+            input,
+            interpolation: Checked::Valid(gltf::json::animation::Interpolation::Step),
+            output,
+        });
+    }
+    root.animations.push(animation);
+}
+
+/// Appends tightly packed floats to `buffer` and returns an accessor for them
+fn push_accessor(
+    root: &mut gltf::json::Root,
+    buffer: &mut Vec<u8>,
+    data: &[f32],
+    type_: gltf::json::accessor::Type,
+) -> gltf::json::Index<gltf::json::Accessor> {
+    let bytes: &[u8] = bytemuck::cast_slice(data);
+    let view = gltf::json::Index::new(root.buffer_views.len() as u32);
+    root.buffer_views.push(gltf::json::buffer::View {
+        buffer: gltf::json::Index::new(0),
+        byte_length: bytes.len() as u32,
+        byte_offset: Some(buffer.len() as u32),
+        byte_stride: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: None,
+    });
+    buffer.extend_from_slice(bytes);
+
+    // Animation inputs must have bounds
+    let (min, max) = match type_ {
+        gltf::json::accessor::Type::Scalar => {
+            let min = data.iter().copied().fold(f32::MAX, f32::min);
+            let max = data.iter().copied().fold(f32::MIN, f32::max);
+            (Some(vec![min].into()), Some(vec![max].into()))
+        }
+        _ => (None, None),
+    };
+
+    root.accessors.push(gltf::json::Accessor {
+        buffer_view: Some(view),
+        byte_offset: Some(0),
+        count: (data.len() / type_.multiplicity()) as u32,
+        component_type: Checked::Valid(GenericComponentType(
+            gltf::json::accessor::ComponentType::F32,
+        )),
+        extensions: Default::default(),
+        extras: Default::default(),
+        type_: Checked::Valid(type_),
+        min,
+        max,
+        name: None,
+        normalized: false,
+        sparse: None,
+    });
+    gltf::json::Index::new(root.accessors.len() as u32 - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::schematic::Schematic;
+
+    use super::Model;
+
+    #[test]
+    fn test_serialize_parts() {
+        let mut door = Schematic::new(4, 4, 4);
+        door.fill(1, 0, 0, 2, 2, 0, Color(120, 80, 40)).unwrap();
+        let mut frame = Schematic::new(4, 4, 4);
+        frame.fill(0, 0, 0, 3, 3, 3, Color(90, 90, 90)).unwrap();
+        frame.carve(1, 0, 0, 2, 2, 0).unwrap();
+
+        let model = Model::Parts(vec![
+            (
+                "house".to_owned(),
+                Model::Parts(vec![
+                    ("door".to_owned(), Model::Schematic(door)),
+                    ("frame".to_owned(), Model::Schematic(frame)),
+                ]),
+            ),
+            (
+                "empty".to_owned(),
+                Model::Schematic(Schematic::new(1, 1, 1)),
+            ),
+        ]);
+
+        let mut glb = Vec::new();
+        model.serialize(&mut glb).unwrap();
+        let gltf = gltf::Gltf::from_slice(&glb).unwrap();
+
+        let roots: Vec<_> = gltf.scenes().next().unwrap().nodes().collect();
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].name(), Some("house"));
+        assert!(roots[0].mesh().is_none());
+        assert_eq!(roots[1].name(), Some("empty"));
+        assert!(roots[1].mesh().is_none());
+
+        let children: Vec<_> = roots[0].children().collect();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].name(), Some("door"));
+        assert_eq!(children[0].mesh().unwrap().name(), Some("door"));
+        assert_eq!(children[1].name(), Some("frame"));
+        assert_eq!(gltf.meshes().count(), 2);
+    }
+
+    #[test]
+    fn test_serialize_frames() {
+        let frames = (0..3)
+            .map(|i| {
+                let mut frame = Schematic::new(3, 1, 1);
+                frame.set(i, 0, 0, Color(255, 100, 0)).unwrap();
+                Model::Schematic(frame)
+            })
+            .collect();
+        let model = Model::Frames {
+            frames,
+            seconds_per_frame: 0.5,
+        };
+
+        let mut glb = Vec::new();
+        model.serialize(&mut glb).unwrap();
+        let gltf = gltf::Gltf::from_slice(&glb).unwrap();
+
+        let root = gltf.scenes().next().unwrap().nodes().next().unwrap();
+        let frames: Vec<_> = root.children().collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1].name(), Some("animation_1"));
+        assert_eq!(frames[0].transform().decomposed().2, [1.; 3]);
+        assert_eq!(frames[1].transform().decomposed().2, [0.; 3]);
+
+        let animation = gltf.animations().next().unwrap();
+        assert_eq!(animation.channels().count(), 3);
+        let input = animation.samplers().next().unwrap().input();
+        assert_eq!(input.count(), 4);
+        assert_eq!(input.max(), Some(serde_json::json!([1.5])));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::color::Color;
+
+use super::{Schematic, Voxel};
+
+impl Schematic {
+    /// Changes every voxel of color `old` to `new`, optionally only within an inclusive region.
+    /// Returns the number of voxels changed, or `None` if the region is out of bounds. Replaced
+    /// voxels keep their transparency and emission but are no longer tagged with a named material.
+    pub fn replace(
+        &mut self,
+        old: Color,
+        new: Color,
+        region: Option<([u8; 3], [u8; 3])>,
+    ) -> Option<usize> {
+        let (min, max) = match region {
+            Some((from, to)) => (
+                [0, 1, 2].map(|i| from[i].min(to[i])),
+                [0, 1, 2].map(|i| from[i].max(to[i])),
+            ),
+            None => (
+                [0; 3],
+                [self.x_size, self.y_size, self.z_size].map(|s| s.saturating_sub(1)),
+            ),
+        };
+        self.get_index(max[0], max[1], max[2])?;
+
+        let mut replaced = 0;
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    if let Some(voxel) = self.get_voxel(x, y, z)?.filter(|v| v.color == old) {
+                        self.set_voxel(
+                            x,
+                            y,
+                            z,
+                            Voxel {
+                                color: new,
+                                material: None,
+                                ..voxel
+                            },
+                        )?;
+                        replaced += 1;
+                    }
+                }
+            }
+        }
+        Some(replaced)
+    }
+
+    /// Sets an opaque `color` on the voxel at the given position and every face-connected voxel
+    /// with the same contents, which may be empty. Returns the number of voxels changed, or `None`
+    /// if the position is out of bounds.
+    pub fn flood_fill(&mut self, x: u8, y: u8, z: u8, color: Color) -> Option<usize> {
+        let voxel = Voxel::from(color);
+        let target = self.get_voxel(x, y, z)?;
+        if target == Some(voxel) {
+            return Some(0);
+        }
+
+        let mut filled = 0;
+        let mut stack = vec![(x, y, z)];
+        while let Some((x, y, z)) = stack.pop() {
+            if self.get_voxel(x, y, z) != Some(target) {
+                continue;
+            }
+            self.set_voxel(x, y, z, voxel)?;
+            filled += 1;
+
+            // Underflow wraps to 255, which is always out of bounds and rejected by `get` above
+            stack.extend_from_slice(&[
+                (x.wrapping_sub(1), y, z),
+                (x + 1, y, z),
+                (x, y.wrapping_sub(1), z),
+                (x, y + 1, z),
+                (x, y, z.wrapping_sub(1)),
+                (x, y, z + 1),
+            ]);
+        }
+        Some(filled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::schematic::Schematic;
+
+    const RED: Color = Color(255, 0, 0);
+    const BLUE: Color = Color(0, 0, 255);
+
+    #[test]
+    fn test_replace() {
+        let mut schem = Schematic::new(4, 4, 4);
+        schem.fill(0, 0, 0, 3, 0, 3, RED).unwrap();
+
+        assert_eq!(
+            schem.replace(RED, BLUE, Some(([1, 0, 1], [0, 0, 0]))),
+            Some(4)
+        );
+        assert_eq!(schem.count(Some(BLUE)), 4);
+        assert_eq!(schem.get(1, 0, 1).unwrap(), Some(BLUE));
+        assert_eq!(schem.get(2, 0, 2).unwrap(), Some(RED));
+
+        assert_eq!(schem.replace(RED, BLUE, None), Some(12));
+        assert_eq!(schem.count(Some(RED)), 0);
+        assert_eq!(schem.count(None), 16);
+
+        assert_eq!(schem.replace(RED, BLUE, Some(([0, 0, 0], [4, 0, 0]))), None);
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        // Filling inside a closed room stops at the walls
+        let mut schem = Schematic::new(5, 5, 5);
+        schem.hollow_box([0, 0, 0], [4, 4, 4], RED).unwrap();
+
+        assert_eq!(schem.flood_fill(2, 2, 2, BLUE), Some(27));
+        assert_eq!(schem.count(Some(BLUE)), 27);
+        assert_eq!(schem.count(Some(RED)), 98);
+
+        assert_eq!(schem.flood_fill(0, 0, 0, BLUE), Some(98));
+        assert_eq!(schem.count(Some(BLUE)), 125);
+        assert_eq!(schem.flood_fill(0, 0, 0, BLUE), Some(0));
+        assert_eq!(schem.flood_fill(5, 0, 0, BLUE), None);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::color::Color;
+
+use super::{Schematic, Voxel};
+
+/// Voxels are considered inside a round shape when their center is within the radius plus half a
+/// voxel. This keeps single-voxel points off the poles of spheres and makes a radius of 0 produce
+/// exactly one voxel.
+const ROUNDING: f32 = 0.5;
+
+impl Schematic {
+    pub fn sphere(
+        &mut self,
+        center: [f32; 3],
+        radius: f32,
+        color: Color,
+        hollow: bool,
+    ) -> Option<()> {
+        self.ellipsoid(center, [radius; 3], color, hollow)
+    }
+
+    pub fn ellipsoid(
+        &mut self,
+        center: [f32; 3],
+        radii: [f32; 3],
+        color: Color,
+        hollow: bool,
+    ) -> Option<()> {
+        let r = radii.map(|r| r + ROUNDING);
+        let min = [0, 1, 2].map(|i| (center[i] - r[i]).floor() as i32);
+        let max = [0, 1, 2].map(|i| (center[i] + r[i]).ceil() as i32);
+
+        self.fill_where(min, max, color, hollow, |x, y, z| {
+            let dx = (x as f32 - center[0]) / r[0];
+            let dy = (y as f32 - center[1]) / r[1];
+            let dz = (z as f32 - center[2]) / r[2];
+            dx * dx + dy * dy + dz * dz <= 1.
+        })
+    }
+
+    /// Vertical cylinder whose base is centered on `base`
+    pub fn cylinder(
+        &mut self,
+        base: [f32; 3],
+        radius: f32,
+        height: u8,
+        color: Color,
+        hollow: bool,
+    ) -> Option<()> {
+        let r = radius + ROUNDING;
+        let (min, max) = vertical_bounds(base, r, height);
+
+        self.fill_where(min, max, color, hollow, |x, _, z| {
+            let dx = x as f32 - base[0];
+            let dz = z as f32 - base[2];
+            dx * dx + dz * dz <= r * r
+        })
+    }
+
+    /// Vertical cone whose base is centered on `base` and tapers to a point at the top
+    pub fn cone(
+        &mut self,
+        base: [f32; 3],
+        radius: f32,
+        height: u8,
+        color: Color,
+        hollow: bool,
+    ) -> Option<()> {
+        let (min, max) = vertical_bounds(base, radius + ROUNDING, height);
+
+        self.fill_where(min, max, color, hollow, |x, y, z| {
+            let r = taper(radius, y as f32 - base[1], height) + ROUNDING;
+            let dx = x as f32 - base[0];
+            let dz = z as f32 - base[2];
+            dx * dx + dz * dz <= r * r
+        })
+    }
+
+    /// Square pyramid whose base is centered on `base`. `radius` is half the width of the base.
+    pub fn pyramid(
+        &mut self,
+        base: [f32; 3],
+        radius: f32,
+        height: u8,
+        color: Color,
+        hollow: bool,
+    ) -> Option<()> {
+        let (min, max) = vertical_bounds(base, radius + ROUNDING, height);
+
+        self.fill_where(min, max, color, hollow, |x, y, z| {
+            let r = taper(radius, y as f32 - base[1], height) + ROUNDING;
+            (x as f32 - base[0]).abs() <= r && (z as f32 - base[2]).abs() <= r
+        })
+    }
+
+    /// Torus lying flat in the XZ plane. `major_radius` is the distance from the center to the
+    /// middle of the ring and `minor_radius` is the thickness of the ring.
+    pub fn torus(
+        &mut self,
+        center: [f32; 3],
+        major_radius: f32,
+        minor_radius: f32,
+        color: Color,
+        hollow: bool,
+    ) -> Option<()> {
+        let r = minor_radius + ROUNDING;
+        let outer = major_radius + r;
+        let min = [center[0] - outer, center[1] - r, center[2] - outer].map(|n| n.floor() as i32);
+        let max = [center[0] + outer, center[1] + r, center[2] + outer].map(|n| n.ceil() as i32);
+
+        self.fill_where(min, max, color, hollow, |x, y, z| {
+            let dx = x as f32 - center[0];
+            let dy = y as f32 - center[1];
+            let dz = z as f32 - center[2];
+            let ring = (dx * dx + dz * dz).sqrt() - major_radius;
+            ring * ring + dy * dy <= r * r
+        })
+    }
+
+    /// Box with walls one voxel thick. Corners are inclusive, like `fill`.
+    pub fn hollow_box(&mut self, from: [u8; 3], to: [u8; 3], color: Color) -> Option<()> {
+        let min = [0, 1, 2].map(|i| from[i].min(to[i]) as i32);
+        let max = [0, 1, 2].map(|i| from[i].max(to[i]) as i32);
+        self.fill_where(min, max, color, true, |_, _, _| true)
+    }
+
+    /// Straight line between two points (inclusive) using 3D Bresenham
+    pub fn line(&mut self, from: [u8; 3], to: [u8; 3], color: Color) -> Option<()> {
+        let points = bresenham(from.map(i32::from), to.map(i32::from));
+        self.set_points(&points, color)
+    }
+
+    /// Sets every voxel in the inclusive box `min..=max` for which `inside` returns true. When
+    /// `hollow` is set, only voxels that have at least one face-adjacent neighbor outside the
+    /// shape are set. Nothing is changed if any of the voxels is out of bounds. The box may reach
+    /// one voxel past the schematic, since round shapes don't fill the corners of their box, but
+    /// boxes reaching further out are rejected before any voxel is visited.
+    fn fill_where<F>(
+        &mut self,
+        min: [i32; 3],
+        max: [i32; 3],
+        color: Color,
+        hollow: bool,
+        inside: F,
+    ) -> Option<()>
+    where
+        F: Fn(i32, i32, i32) -> bool,
+    {
+        let size = [self.x_size, self.y_size, self.z_size].map(i32::from);
+        if (0..3).any(|i| min[i] < -1 || max[i] > size[i]) {
+            return None;
+        }
+
+        let in_box = |x: i32, y: i32, z: i32| {
+            (min[0]..=max[0]).contains(&x)
+                && (min[1]..=max[1]).contains(&y)
+                && (min[2]..=max[2]).contains(&z)
+                && inside(x, y, z)
+        };
+
+        let mut points = Vec::new();
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    if !in_box(x, y, z) {
+                        continue;
+                    }
+
+                    let surface = [
+                        (x - 1, y, z),
+                        (x + 1, y, z),
+                        (x, y - 1, z),
+                        (x, y + 1, z),
+                        (x, y, z - 1),
+                        (x, y, z + 1),
+                    ]
+                    .iter()
+                    .any(|&(nx, ny, nz)| !in_box(nx, ny, nz));
+
+                    if !hollow || surface {
+                        points.push([x, y, z]);
+                    }
+                }
+            }
+        }
+
+        self.set_points(&points, color)
+    }
+
+    fn set_points(&mut self, points: &[[i32; 3]], color: Color) -> Option<()> {
+        let mut indices = Vec::with_capacity(points.len());
+        for &[x, y, z] in points {
+            let x = u8::try_from(x).ok()?;
+            let y = u8::try_from(y).ok()?;
+            let z = u8::try_from(z).ok()?;
+            indices.push(self.get_index(x, y, z)?);
+        }
+
+        for index in indices {
+            self.blocks.set(index, Some(Voxel::from(color)));
+        }
+        Some(())
+    }
+}
+
+fn vertical_bounds(base: [f32; 3], radius: f32, height: u8) -> ([i32; 3], [i32; 3]) {
+    let min = [
+        (base[0] - radius).floor() as i32,
+        base[1].round() as i32,
+        (base[2] - radius).floor() as i32,
+    ];
+    let max = [
+        (base[0] + radius).ceil() as i32,
+        base[1].round() as i32 + height as i32 - 1,
+        (base[2] + radius).ceil() as i32,
+    ];
+    (min, max)
+}
+
+/// Radius of a linearly tapering shape `dy` voxels above its base. The top layer has a radius of 0.
+fn taper(radius: f32, dy: f32, height: u8) -> f32 {
+    if height <= 1 {
+        return radius;
+    }
+    radius * (1. - dy / (height - 1) as f32)
+}
+
+fn bresenham(from: [i32; 3], to: [i32; 3]) -> Vec<[i32; 3]> {
+    let delta = [0, 1, 2].map(|i| (to[i] - from[i]).abs());
+    let step = [0, 1, 2].map(|i| (to[i] - from[i]).signum());
+
+    // Step one voxel at a time along the axis with the largest change and accumulate error on the
+    // other two
+    let major = (0..3).max_by_key(|&i| delta[i]).unwrap();
+    let (a, b) = ((major + 1) % 3, (major + 2) % 3);
+
+    let mut point = from;
+    let mut err_a = 2 * delta[a] - delta[major];
+    let mut err_b = 2 * delta[b] - delta[major];
+    let mut points = Vec::with_capacity(delta[major] as usize + 1);
+    points.push(point);
+
+    for _ in 0..delta[major] {
+        point[major] += step[major];
+        if err_a > 0 {
+            point[a] += step[a];
+            err_a -= 2 * delta[major];
+        }
+        if err_b > 0 {
+            point[b] += step[b];
+            err_b -= 2 * delta[major];
+        }
+        err_a += 2 * delta[a];
+        err_b += 2 * delta[b];
+        points.push(point);
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::schematic::Schematic;
+
+    use super::bresenham;
+
+    const RED: Color = Color(255, 0, 0);
+
+    fn count(schem: &Schematic) -> usize {
+        let mut count = 0;
+        for x in 0..schem.x_size() {
+            for y in 0..schem.y_size() {
+                for z in 0..schem.z_size() {
+                    if schem.get(x, y, z).unwrap().is_some() {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_sphere() {
+        let mut schem = Schematic::new(11, 11, 11);
+        schem.sphere([5., 5., 5.], 0., RED, false).unwrap();
+        assert_eq!(count(&schem), 1);
+
+        let mut schem = Schematic::new(11, 11, 11);
+        schem.sphere([5., 5., 5.], 5., RED, false).unwrap();
+        assert_eq!(schem.get(5, 5, 5).unwrap(), Some(RED));
+        assert_eq!(schem.get(0, 5, 5).unwrap(), Some(RED));
+        assert_eq!(schem.get(0, 0, 0).unwrap(), None);
+
+        let mut hollow = Schematic::new(11, 11, 11);
+        hollow.sphere([5., 5., 5.], 5., RED, true).unwrap();
+        assert_eq!(hollow.get(5, 5, 5).unwrap(), None);
+        assert_eq!(hollow.get(0, 5, 5).unwrap(), Some(RED));
+        assert!(count(&hollow) < count(&schem));
+    }
+
+    #[test]
+    fn test_out_of_bounds_changes_nothing() {
+        let mut schem = Schematic::new(10, 10, 10);
+        assert_eq!(schem.sphere([1., 1., 1.], 3., RED, false), None);
+        assert_eq!(schem.cylinder([5., 8., 5.], 2., 5, RED, false), None);
+        assert_eq!(schem.sphere([5., 5., 5.], 1e6, RED, false), None);
+        assert_eq!(schem.sphere([f32::MAX, 5., 5.], 1., RED, true), None);
+        assert_eq!(count(&schem), 0);
+    }
+
+    #[test]
+    fn test_cylinder_cone_pyramid() {
+        let mut schem = Schematic::new(9, 5, 9);
+        schem.cylinder([4., 0., 4.], 2., 5, RED, false).unwrap();
+        assert_eq!(count(&schem), 21 * 5);
+
+        let mut schem = Schematic::new(9, 5, 9);
+        schem.cone([4., 0., 4.], 4., 5, RED, false).unwrap();
+        assert_eq!(schem.get(4, 4, 4).unwrap(), Some(RED));
+        assert_eq!(schem.get(3, 4, 4).unwrap(), None);
+
+        let mut schem = Schematic::new(9, 5, 9);
+        schem.pyramid([4., 0., 4.], 4., 5, RED, false).unwrap();
+        assert_eq!(schem.get(0, 0, 0).unwrap(), Some(RED));
+        assert_eq!(schem.get(4, 4, 4).unwrap(), Some(RED));
+        assert_eq!(schem.get(3, 4, 3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_torus() {
+        let mut schem = Schematic::new(11, 3, 11);
+        schem.torus([5., 1., 5.], 4., 0., RED, false).unwrap();
+        assert_eq!(schem.get(5, 1, 5).unwrap(), None);
+        assert_eq!(schem.get(1, 1, 5).unwrap(), Some(RED));
+        assert_eq!(schem.get(5, 1, 9).unwrap(), Some(RED));
+        assert_eq!(schem.get(1, 0, 5).unwrap(), None);
+    }
+
+    #[test]
+    fn test_hollow_box() {
+        let mut schem = Schematic::new(5, 5, 5);
+        schem.hollow_box([4, 4, 4], [0, 0, 0], RED).unwrap();
+        assert_eq!(count(&schem), 5 * 5 * 5 - 3 * 3 * 3);
+        assert_eq!(schem.get(2, 2, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_bresenham() {
+        assert_eq!(bresenham([0, 0, 0], [0, 0, 0]), vec![[0, 0, 0]]);
+        assert_eq!(
+            bresenham([0, 0, 0], [3, 0, 0]),
+            vec![[0, 0, 0], [1, 0, 0], [2, 0, 0], [3, 0, 0]]
+        );
+        assert_eq!(
+            bresenham([2, 2, 2], [0, 0, 0]),
+            vec![[2, 2, 2], [1, 1, 1], [0, 0, 0]]
+        );
+
+        let line = bresenham([0, 0, 0], [9, 4, 2]);
+        assert_eq!(line.len(), 10);
+        assert_eq!(line.first(), Some(&[0, 0, 0]));
+        assert_eq!(line.last(), Some(&[9, 4, 2]));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use super::{Model, Schematic};
+
+/// Measurements of a model that hint at how well it was built
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub voxels: usize,
+    /// Number of distinct colors
+    pub colors: usize,
+    /// Fraction of the bounding box volume that is filled, from 0 to 1
+    pub fill: f32,
+    /// Fraction of voxels that aren't connected to the bottom layer of their schematic, from 0 to 1
+    pub floating: f32,
+}
+
+impl Model {
+    /// Stats over every schematic in the model, including all parts and frames
+    pub fn stats(&self) -> Stats {
+        let mut schematics = Vec::new();
+        self.collect_schematics(&mut schematics);
+
+        let mut voxels = 0;
+        let mut volume = 0;
+        let mut floating = 0;
+        let mut colors = HashSet::new();
+        for schematic in schematics {
+            let (min, max) = match schematic.bounding_box() {
+                Some(b) => b,
+                None => continue,
+            };
+            volume += (0..3)
+                .map(|i| (max[i] - min[i]) as usize + 1)
+                .product::<usize>();
+            voxels += schematic.count(None);
+            floating += schematic.floating(min[1]);
+            colors.extend(
+                schematic
+                    .positions()
+                    .filter_map(|(x, y, z)| schematic.get(x, y, z).flatten()),
+            );
+        }
+
+        if voxels == 0 {
+            return Stats {
+                voxels: 0,
+                colors: 0,
+                fill: 0.,
+                floating: 0.,
+            };
+        }
+
+        Stats {
+            voxels,
+            colors: colors.len(),
+            fill: voxels as f32 / volume as f32,
+            floating: floating as f32 / voxels as f32,
+        }
+    }
+
+    fn collect_schematics<'a>(&'a self, schematics: &mut Vec<&'a Schematic>) {
+        match self {
+            Model::Schematic(schematic) => schematics.push(schematic),
+            Model::Parts(parts) => {
+                for (_, part) in parts {
+                    part.collect_schematics(schematics);
+                }
+            }
+            Model::Frames { frames, .. } => {
+                for frame in frames {
+                    frame.collect_schematics(schematics);
+                }
+            }
+        }
+    }
+}
+
+impl Schematic {
+    /// Number of voxels that can't be reached from a voxel in layer `ground` by moving between
+    /// face-connected voxels
+    fn floating(&self, ground: u8) -> usize {
+        let mut reached =
+            vec![false; self.x_size as usize * self.y_size as usize * self.z_size as usize];
+        let mut stack: Vec<(u8, u8, u8)> = self
+            .positions()
+            .filter(|&(x, y, z)| y == ground && self.get(x, y, z) != Some(None))
+            .collect();
+
+        let mut connected = 0;
+        while let Some((x, y, z)) = stack.pop() {
+            let index = match self.get_index(x, y, z) {
+                Some(i) => i,
+                None => continue,
+            };
+            if reached[index] || self.blocks.get(index).is_none() {
+                continue;
+            }
+            reached[index] = true;
+            connected += 1;
+
+            // Underflow wraps to 255, which is always out of bounds
+            stack.extend_from_slice(&[
+                (x.wrapping_sub(1), y, z),
+                (x + 1, y, z),
+                (x, y.wrapping_sub(1), z),
+                (x, y + 1, z),
+                (x, y, z.wrapping_sub(1)),
+                (x, y, z + 1),
+            ]);
+        }
+
+        self.count(None) - connected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::schematic::{Model, Schematic};
+
+    #[test]
+    fn test_stats() {
+        let mut schem = Schematic::new(10, 10, 10);
+        schem.fill(2, 2, 2, 5, 2, 3, Color(255, 0, 0)).unwrap();
+        schem.set(3, 3, 2, Color(0, 255, 0)).unwrap();
+        // Not connected to the bottom layer
+        schem.set(2, 5, 3, Color(0, 0, 255)).unwrap();
+
+        let stats = Model::Schematic(schem.clone()).stats();
+        assert_eq!(stats.voxels, 10);
+        assert_eq!(stats.colors, 3);
+        assert_eq!(stats.fill, 10. / (4. * 4. * 2.));
+        assert_eq!(stats.floating, 0.1);
+
+        let parts = Model::Parts(vec![
+            ("a".to_owned(), Model::Schematic(schem)),
+            ("b".to_owned(), Model::Schematic(Schematic::new(1, 1, 1))),
+        ]);
+        assert_eq!(parts.stats().voxels, 10);
+        assert_eq!(Model::Schematic(Schematic::new(2, 2, 2)).stats().voxels, 0);
+    }
+}
@@ -0,0 +1,191 @@
+use strum_macros::EnumString;
+
+use super::Schematic;
+
+#[derive(Clone, Copy, Debug, EnumString, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Schematic {
+    /// Rotates counter-clockwise by `quarter_turns` * 90 degrees when looking from the positive
+    /// side of `axis` towards the origin. Negative values rotate clockwise. The sizes of the other
+    /// two axes are swapped on odd turns.
+    pub fn rotate(&mut self, axis: Axis, quarter_turns: i32) {
+        for _ in 0..quarter_turns.rem_euclid(4) {
+            self.rotate_once(axis);
+        }
+    }
+
+    fn rotate_once(&mut self, axis: Axis) {
+        let (xs, ys, zs) = (self.x_size, self.y_size, self.z_size);
+        *self = match axis {
+            Axis::X => self.remap([xs, zs, ys], |x, y, z| [x, zs - 1 - z, y]),
+            Axis::Y => self.remap([zs, ys, xs], |x, y, z| [z, y, xs - 1 - x]),
+            Axis::Z => self.remap([ys, xs, zs], |x, y, z| [ys - 1 - y, x, z]),
+        };
+    }
+
+    /// Flips the schematic along `axis`
+    pub fn mirror(&mut self, axis: Axis) {
+        let (xs, ys, zs) = (self.x_size, self.y_size, self.z_size);
+        *self = match axis {
+            Axis::X => self.remap([xs, ys, zs], |x, y, z| [xs - 1 - x, y, z]),
+            Axis::Y => self.remap([xs, ys, zs], |x, y, z| [x, ys - 1 - y, z]),
+            Axis::Z => self.remap([xs, ys, zs], |x, y, z| [x, y, zs - 1 - z]),
+        };
+    }
+
+    /// Moves every voxel by the given offset without resizing. Voxels that end up out of bounds
+    /// are discarded.
+    pub fn shift(&mut self, dx: i32, dy: i32, dz: i32) {
+        let mut shifted = self.blank(self.x_size, self.y_size, self.z_size);
+        for (x, y, z) in self.positions() {
+            let voxel = match self.get_voxel(x, y, z) {
+                Some(Some(v)) => v,
+                _ => continue,
+            };
+
+            // Offsets so large that they overflow are out of bounds too
+            let moved = [(x, dx), (y, dy), (z, dz)]
+                .map(|(n, d)| (n as i32).checked_add(d).and_then(|n| u8::try_from(n).ok()));
+            if let [Some(nx), Some(ny), Some(nz)] = moved {
+                // Out of bounds voxels are dropped by `set_voxel`
+                shifted.set_voxel(nx, ny, nz, voxel);
+            }
+        }
+        *self = shifted;
+    }
+
+    /// Returns the inclusive minimum and maximum corners of the non-empty voxels, or `None` if the
+    /// schematic is empty
+    pub fn bounding_box(&self) -> Option<([u8; 3], [u8; 3])> {
+        let mut bounds: Option<([u8; 3], [u8; 3])> = None;
+        for (x, y, z) in self.positions() {
+            if self.get(x, y, z) != Some(None) {
+                let (min, max) = bounds.get_or_insert(([x, y, z], [x, y, z]));
+                *min = [min[0].min(x), min[1].min(y), min[2].min(z)];
+                *max = [max[0].max(x), max[1].max(y), max[2].max(z)];
+            }
+        }
+        bounds
+    }
+
+    /// Shrinks the schematic to the bounding box of its non-empty voxels. Empty schematics are left
+    /// unchanged.
+    pub fn crop(&mut self) {
+        let (min, max) = match self.bounding_box() {
+            Some(b) => b,
+            None => return,
+        };
+
+        let size = [0, 1, 2].map(|i| max[i] - min[i] + 1);
+        *self = self.remap(size, |x, y, z| [x - min[0], y - min[1], z - min[2]]);
+    }
+
+    /// Creates a schematic of the given size where every voxel at `(x, y, z)` in `self` is moved
+    /// to the position returned by `f`. `f` is only called with non-empty positions and must
+    /// return an in-bounds position.
+    fn remap<F>(&self, size: [u8; 3], f: F) -> Schematic
+    where
+        F: Fn(u8, u8, u8) -> [u8; 3],
+    {
+        let mut remapped = self.blank(size[0], size[1], size[2]);
+        for (x, y, z) in self.positions() {
+            if let Some(Some(voxel)) = self.get_voxel(x, y, z) {
+                let [nx, ny, nz] = f(x, y, z);
+                remapped.set_voxel(nx, ny, nz, voxel).unwrap();
+            }
+        }
+        remapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::color::Color;
+    use crate::schematic::Schematic;
+
+    use super::Axis;
+
+    const RED: Color = Color(255, 0, 0);
+
+    #[test]
+    fn test_axis_from_str() {
+        assert_eq!(Axis::from_str("x"), Ok(Axis::X));
+        assert_eq!(Axis::from_str("Y"), Ok(Axis::Y));
+        assert!(Axis::from_str("w").is_err());
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut schem = Schematic::new(4, 2, 3);
+        schem.set(3, 0, 0, RED).unwrap();
+
+        schem.rotate(Axis::Y, 1);
+        assert_eq!((schem.x_size(), schem.y_size(), schem.z_size()), (3, 2, 4));
+        assert_eq!(schem.get(0, 0, 0).unwrap(), Some(RED));
+
+        schem.rotate(Axis::Y, -1);
+        assert_eq!((schem.x_size(), schem.y_size(), schem.z_size()), (4, 2, 3));
+        assert_eq!(schem.get(3, 0, 0).unwrap(), Some(RED));
+
+        schem.rotate(Axis::Z, 1);
+        assert_eq!((schem.x_size(), schem.y_size(), schem.z_size()), (2, 4, 3));
+        assert_eq!(schem.get(1, 3, 0).unwrap(), Some(RED));
+
+        schem.rotate(Axis::X, 2);
+        assert_eq!((schem.x_size(), schem.y_size(), schem.z_size()), (2, 4, 3));
+        assert_eq!(schem.get(1, 0, 2).unwrap(), Some(RED));
+
+        schem.rotate(Axis::X, 4);
+        assert_eq!(schem.get(1, 0, 2).unwrap(), Some(RED));
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut schem = Schematic::new(4, 4, 4);
+        schem.set(0, 1, 2, RED).unwrap();
+        schem.mirror(Axis::X);
+        assert_eq!(schem.get(3, 1, 2).unwrap(), Some(RED));
+        schem.mirror(Axis::Y);
+        assert_eq!(schem.get(3, 2, 2).unwrap(), Some(RED));
+        schem.mirror(Axis::Z);
+        assert_eq!(schem.get(3, 2, 1).unwrap(), Some(RED));
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut schem = Schematic::new(4, 4, 4);
+        schem.set(0, 0, 0, RED).unwrap();
+        schem.set(3, 3, 3, RED).unwrap();
+        schem.shift(1, 0, 0);
+        assert_eq!(schem.get(1, 0, 0).unwrap(), Some(RED));
+        assert_eq!(schem.get(0, 0, 0).unwrap(), None);
+        assert_eq!(schem.bounding_box(), Some(([1, 0, 0], [1, 0, 0])));
+
+        schem.shift(i32::MAX, 0, 0);
+        assert_eq!(schem.bounding_box(), None);
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut schem = Schematic::new(10, 10, 10);
+        schem.crop();
+        assert_eq!(schem.x_size(), 10);
+
+        schem.set(2, 3, 4, RED).unwrap();
+        schem.set(5, 3, 6, RED).unwrap();
+        assert_eq!(schem.bounding_box(), Some(([2, 3, 4], [5, 3, 6])));
+
+        schem.crop();
+        assert_eq!((schem.x_size(), schem.y_size(), schem.z_size()), (4, 1, 3));
+        assert_eq!(schem.get(0, 0, 0).unwrap(), Some(RED));
+        assert_eq!(schem.get(3, 0, 2).unwrap(), Some(RED));
+    }
+}
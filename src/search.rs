@@ -0,0 +1,210 @@
+mod embedder;
+mod hashing;
+mod openai;
+
+pub use embedder::Embedder;
+pub use hashing::HashingEmbedder;
+pub use openai::OpenAiEmbedder;
+
+use rocket::tokio::sync::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::nlp::Usage;
+use crate::storage::ObjectStorage;
+
+/// Entries are stored one per record as `search/<model>/<id>`, apart from generations
+fn entry_prefix(model: &str) -> String {
+    format!("search/{}/", model)
+}
+
+/// A generation and the embedding of the prompt that produced it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IndexEntry {
+    pub id: String,
+    /// Account that made the generation, see `usage::account_id`. Only that account finds it.
+    /// Entries stored before accounts were recorded have none and are never found.
+    #[serde(default)]
+    pub account: String,
+    pub prompt: String,
+    pub url: String,
+    /// Lua that built the generation. Only generations whose code ran are indexed.
+    pub code: String,
+    /// Embedding model that produced `embedding`
+    pub model: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Finds earlier generations with prompts similar to a query. The index is kept in memory and
+/// every entry is stored as a record of its own, so inserting never rewrites other entries.
+pub struct SearchIndex {
+    embedder: Box<dyn Embedder>,
+    entries: RwLock<Vec<IndexEntry>>,
+}
+
+impl SearchIndex {
+    /// Loads the stored entries embedded by the model of `embedder`. Entries of other models
+    /// can't be compared, so they are left in storage for when that model is used again.
+    pub async fn load(
+        embedder: Box<dyn Embedder>,
+        storage: &dyn ObjectStorage,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        for id in storage
+            .list_records(&entry_prefix(embedder.model()))
+            .await?
+        {
+            // A broken entry only loses that generation, not the index
+            if let Some(data) = storage.get_record(&id).await? {
+                match serde_json::from_slice::<IndexEntry>(&data) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => tracing::warn!("skipping search entry {}: {}", id, e),
+                }
+            }
+        }
+        tracing::info!("loaded {} search entries", entries.len());
+
+        Ok(SearchIndex {
+            embedder,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub fn embedder(&self) -> &dyn Embedder {
+        self.embedder.as_ref()
+    }
+
+    /// Adds the generation to the index, replacing any earlier entry with the same id. `prompt` is
+    /// only embedded if `embedding` is `None`, in which case the tokens are added to `usage`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn insert(
+        &self,
+        storage: &dyn ObjectStorage,
+        account: &str,
+        id: &str,
+        prompt: &str,
+        embedding: Option<Vec<f32>>,
+        url: &str,
+        code: &str,
+        usage: &mut Usage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let embedding = match embedding {
+            Some(embedding) => embedding,
+            None => self.embedder.embed(prompt, usage).await?,
+        };
+        let entry = IndexEntry {
+            id: id.to_owned(),
+            account: account.to_owned(),
+            prompt: prompt.to_owned(),
+            url: url.to_owned(),
+            code: code.to_owned(),
+            model: self.embedder.model().to_owned(),
+            embedding,
+        };
+
+        let id = format!("{}{}", entry_prefix(&entry.model), entry.id);
+        storage
+            .put_record(&id, &serde_json::to_vec(&entry)?)
+            .await?;
+
+        let mut entries = self.entries.write().await;
+        entries.retain(|e| e.id != entry.id);
+        entries.push(entry);
+        Ok(())
+    }
+
+    /// At most `limit` generations of `account` with the most similar prompts, most similar first.
+    /// The tokens spent embedding `query` are added to `usage`.
+    pub async fn nearest(
+        &self,
+        account: &str,
+        query: &str,
+        limit: usize,
+        usage: &mut Usage,
+    ) -> Result<Vec<(f32, IndexEntry)>, Box<dyn std::error::Error>> {
+        let embedding = self.embedder.embed(query, usage).await?;
+        Ok(rank(&self.entries.read().await, account, &embedding, limit))
+    }
+}
+
+fn rank(
+    entries: &[IndexEntry],
+    account: &str,
+    embedding: &[f32],
+    limit: usize,
+) -> Vec<(f32, IndexEntry)> {
+    let mut matches: Vec<_> = entries
+        .iter()
+        .filter(|e| !e.account.is_empty() && e.account == account)
+        .filter_map(|e| Some((similarity(&e.embedding, embedding)?, e)))
+        .collect();
+    matches.sort_by(|a, b| b.0.total_cmp(&a.0));
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(score, e)| (score, e.clone()))
+        .collect()
+}
+
+/// Cosine similarity from -1 to 1, or `None` if the vectors can't be compared
+pub fn similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let length = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let lengths = length(a) * length(b);
+    (lengths > 0.).then(|| dot / lengths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank, Embedder, HashingEmbedder, IndexEntry};
+    use crate::nlp::Usage;
+
+    async fn embed(text: &str) -> Vec<f32> {
+        HashingEmbedder
+            .embed(text, &mut Usage::default())
+            .await
+            .unwrap()
+    }
+
+    async fn entry(account: &str, prompt: &str) -> IndexEntry {
+        IndexEntry {
+            id: prompt.replace(' ', "-"),
+            account: account.to_owned(),
+            prompt: prompt.to_owned(),
+            url: String::new(),
+            code: String::new(),
+            model: HashingEmbedder.model().to_owned(),
+            embedding: embed(prompt).await,
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_rank() {
+        let entries = vec![
+            entry("a", "a tall pine tree").await,
+            entry("a", "a red brick house with a chimney").await,
+            entry("a", "a small wooden house").await,
+            entry("b", "a wooden house").await,
+            entry("", "wooden houses").await,
+        ];
+
+        let query = embed("wooden houses").await;
+        let matches = rank(&entries, "a", &query, 2);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].1.id, "a-small-wooden-house");
+        assert_eq!(matches[1].1.id, "a-red-brick-house-with-a-chimney");
+        assert!(matches[0].0 > matches[1].0);
+
+        // Only the caller's own generations are found
+        let matches = rank(&entries, "b", &query, 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].1.id, "a-wooden-house");
+        assert!(rank(&entries, "", &query, 10).is_empty());
+
+        // Embeddings are deterministic
+        assert_eq!(embed("a tree").await, embed("a tree").await);
+    }
+}
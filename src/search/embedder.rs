@@ -0,0 +1,18 @@
+use rocket::async_trait;
+
+use crate::nlp::Usage;
+
+/// Turns text into a vector whose direction captures its meaning, so that similar prompts end up
+/// close to each other
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Identifies the embedding space. Vectors from different models can't be compared.
+    fn model(&self) -> &str;
+
+    /// Adds the tokens spent to `usage`
+    async fn embed(
+        &self,
+        text: &str,
+        usage: &mut Usage,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>>;
+}
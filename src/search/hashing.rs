@@ -0,0 +1,64 @@
+use rocket::async_trait;
+
+use super::Embedder;
+use crate::nlp::Usage;
+
+const DIMENSIONS: usize = 256;
+
+/// Deterministic local embedder that hashes words and their character trigrams into a fixed
+/// number of buckets. It only captures shared vocabulary, not meaning, but needs no network and
+/// gives the same vectors on every run, which makes it useful for tests and local development.
+pub struct HashingEmbedder;
+
+#[async_trait]
+impl Embedder for HashingEmbedder {
+    fn model(&self) -> &str {
+        "hashing-256"
+    }
+
+    async fn embed(
+        &self,
+        text: &str,
+        _: &mut Usage,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        Ok(embed(text))
+    }
+}
+
+fn embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.; DIMENSIONS];
+    let text = text.to_lowercase();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        add_feature(&mut vector, word.as_bytes(), 1.);
+
+        // Trigrams match different forms of a word, like "tree" and "trees"
+        let padded: Vec<char> = format!(" {} ", word).chars().collect();
+        for trigram in padded.windows(3) {
+            let trigram: String = trigram.iter().collect();
+            add_feature(&mut vector, trigram.as_bytes(), 0.5);
+        }
+    }
+
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length > 0. {
+        vector.iter_mut().for_each(|x| *x /= length);
+    }
+    vector
+}
+
+fn add_feature(vector: &mut [f32], feature: &[u8], weight: f32) {
+    let hash = fnv1a(feature);
+    let sign = if hash >> 63 == 0 { 1. } else { -1. };
+    vector[(hash % DIMENSIONS as u64) as usize] += sign * weight;
+}
+
+/// Unlike `DefaultHasher`, FNV-1a is guaranteed to give the same hashes across Rust versions, so
+/// stored vectors stay comparable
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
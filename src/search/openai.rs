@@ -0,0 +1,73 @@
+use rocket::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Embedder;
+use crate::nlp::Usage;
+
+const MODEL: &str = "text-embedding-ada-002";
+
+/// Embeddings from the OpenAI API
+pub struct OpenAiEmbedder {
+    api_key: String,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(api_key: String) -> Self {
+        OpenAiEmbedder { api_key }
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    data: Vec<Embedding>,
+    usage: ResponseUsage,
+}
+
+/// Embeddings have no completion, so the API leaves out `completion_tokens`
+#[derive(Deserialize)]
+struct ResponseUsage {
+    prompt_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct Embedding {
+    embedding: Vec<f32>,
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    fn model(&self) -> &str {
+        MODEL
+    }
+
+    async fn embed(
+        &self,
+        text: &str,
+        usage: &mut Usage,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let response: Response = reqwest::Client::new()
+            .post("https://api.openai.com/v1/embeddings")
+            .json(&json!({
+                "model": MODEL,
+                "input": text,
+            }))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        usage.add(Usage {
+            prompt_tokens: response.usage.prompt_tokens,
+            completion_tokens: 0,
+            total_tokens: response.usage.total_tokens,
+        });
+
+        match response.data.into_iter().next() {
+            Some(e) => Ok(e.embedding),
+            None => Err("response contained no embedding".into()),
+        }
+    }
+}
@@ -1,58 +1,916 @@
-use std::time::Instant;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use crate::nlp;
+use crate::cache::{CacheKey, CachedGeneration, PromptCache};
+use crate::nlp::{
+    self, ExampleLibrary, Generation, NlpError, Plan, Prompts, Score, Selector, Session,
+    SessionStore, Usage,
+};
+use crate::search::SearchIndex;
 use crate::storage::ObjectStorage;
-use rocket::{http::Status, post, routes, State};
+use crate::usage::{self, Ledger, PriceTable, UsageEntry, UsageKind, UsageSummary};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, status::Custom, Responder};
+use rocket::serde::json::Json;
+use rocket::{get, post, routes, State};
+use serde::{Deserialize, Serialize};
 
 struct Server {
     openai_api_key: String,
+    /// Token for admin routes, which are disabled without one
+    admin_token: Option<String>,
     object_storage: Box<dyn ObjectStorage>,
+    sessions: SessionStore,
+    prices: PriceTable,
+    ledger: Ledger,
+    cache: PromptCache,
+    search: SearchIndex,
+    examples: ExampleLibrary,
+    /// Replaced by `/prompts/reload`. Requests keep the prompts they started with.
+    prompts: RwLock<Arc<Prompts>>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     config: rocket::Config,
     openai_api_key: String,
+    admin_token: Option<String>,
     object_storage: Box<dyn ObjectStorage>,
+    prices: PriceTable,
+    cache_ttl: Duration,
+    session_ttl: Duration,
+    search: SearchIndex,
+    examples: ExampleLibrary,
+    prompts: Prompts,
 ) {
     rocket::custom(config)
         .manage(Server {
             openai_api_key,
+            admin_token,
             object_storage,
+            sessions: SessionStore::new(session_ttl),
+            prices,
+            ledger: Ledger::default(),
+            cache: PromptCache::new(cache_ttl),
+            search,
+            examples,
+            prompts: RwLock::new(Arc::new(prompts)),
         })
-        .mount("/", routes![generate])
+        .mount(
+            "/",
+            routes![
+                generate,
+                generate_v2,
+                edit,
+                get_usage,
+                search,
+                reload_prompts
+            ],
+        )
         .launch()
         .await
         .unwrap();
 }
 
-#[post("/generate?<id>&<prompt>")]
-async fn generate(server: &State<Server>, id: &str, prompt: &str) -> Result<String, Status> {
+/// Account of the caller, derived from the `X-Api-Key` header and used to attribute token usage.
+/// Callers without a key share the `ANONYMOUS` account.
+struct Account(String);
+
+const ANONYMOUS: &str = "anonymous";
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Account {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let account = match request.headers().get_one("X-Api-Key") {
+            Some(key) => usage::account_id(key),
+            None => ANONYMOUS.to_owned(),
+        };
+        Outcome::Success(Account(account))
+    }
+}
+
+/// Caller that sent `Authorization: Bearer <token>` with the admin token. Admin routes are
+/// disabled if no token is configured.
+struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let expected = match request.rocket().state::<Server>() {
+            Some(Server {
+                admin_token: Some(token),
+                ..
+            }) => token,
+            _ => return Outcome::Failure((Status::Forbidden, ())),
+        };
+
+        let given = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "));
+        match given {
+            Some(given) if constant_time_eq(given.as_bytes(), expected.as_bytes()) => {
+                Outcome::Success(Admin)
+            }
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Compares without returning early, so the time taken doesn't tell how much of a guess matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Serialize)]
+struct GenerationResponse {
+    id: String,
+    url: String,
+    seed: u32,
+    /// Pass this to later generations to continue the design session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
+    /// Scores of every sample, including the one that was kept
+    scores: Vec<Score>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plan: Option<Plan>,
+    usage: Usage,
+    /// Cost in USD, if the model has a price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost: Option<f64>,
+    /// Set if an earlier generation was returned instead of generating a new one
+    cached: bool,
+    /// Version of the prompt templates the code was generated with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_version: Option<String>,
+}
+
+/// Everything needed to edit a generation later
+#[derive(Deserialize, Serialize)]
+struct GenerationRecord {
+    prompt: String,
+    /// Plan the code was written from. Edited versions have none.
+    #[serde(default)]
+    plan: Option<Plan>,
+    /// Edit instructions applied to the original prompt, oldest first
+    edits: Vec<String>,
+    code: String,
+    seed: u32,
+    /// Id of the first version. Later versions are stored as `<root>-v<version>`.
+    root: String,
+    version: u32,
+    /// Tokens spent on this version alone
+    #[serde(default)]
+    usage: Usage,
+    #[serde(default)]
+    cost: Option<f64>,
+    /// Version of the prompt templates used for this version
+    #[serde(default)]
+    prompt_version: Option<String>,
+}
+
+/// Error response of the generation routes. Generations whose samples all failed report why and
+/// the scores of the samples, everything else only a status.
+enum ApiError {
+    Status(Status),
+    Failed(FailedGeneration),
+}
+
+#[derive(Serialize)]
+struct FailedGeneration {
+    error: String,
+    scores: Vec<Score>,
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        ApiError::Status(status)
+    }
+}
+
+impl From<NlpError> for ApiError {
+    fn from(error: NlpError) -> Self {
+        match error {
+            NlpError::Lua(e, scores) => ApiError::Failed(FailedGeneration {
+                error: e.to_string(),
+                scores,
+            }),
+            _ => ApiError::Status(Status::InternalServerError),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            ApiError::Status(status) => status.respond_to(request),
+            ApiError::Failed(failed) => {
+                Custom(Status::UnprocessableEntity, Json(failed)).respond_to(request)
+            }
+        }
+    }
+}
+
+/// Query parameters shared by `/generate` and `/v2/generate`
+struct GenerateQuery<'r> {
+    id: &'r str,
+    prompt: &'r str,
+    seed: Option<u32>,
+    session: Option<&'r str>,
+    samples: Option<usize>,
+    plan: Option<bool>,
+    cache: Option<&'r str>,
+}
+
+/// Plain URL of the model, as `/generate` returned before it had anything else to report
+struct UrlResponse {
+    url: String,
+    seed: u32,
+    session: Option<String>,
+}
+
+impl<'r> Responder<'r, 'static> for UrlResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = self.url.respond_to(request)?;
+        response.set_raw_header("X-Seed", self.seed.to_string());
+        if let Some(session) = self.session {
+            response.set_raw_header("X-Session", session);
+        }
+        Ok(response)
+    }
+}
+
+/// Generates a model and responds with its URL. The seed and session are sent in the `X-Seed` and
+/// `X-Session` headers. `/v2/generate` takes the same parameters and responds with everything
+/// else as JSON.
+#[post("/generate?<id>&<prompt>&<seed>&<session>&<samples>&<plan>&<cache>")]
+#[allow(clippy::too_many_arguments)]
+async fn generate(
+    server: &State<Server>,
+    account: Account,
+    id: &str,
+    prompt: &str,
+    seed: Option<u32>,
+    session: Option<&str>,
+    samples: Option<usize>,
+    plan: Option<bool>,
+    cache: Option<&str>,
+) -> Result<UrlResponse, Status> {
+    let query = GenerateQuery {
+        id,
+        prompt,
+        seed,
+        session,
+        samples,
+        plan,
+        cache,
+    };
+    // Clients of the plain response only ever got a status
+    let response = match generate_model(server, &account, query).await {
+        Ok(response) => response,
+        Err(ApiError::Status(status)) => return Err(status),
+        Err(ApiError::Failed(_)) => return Err(Status::InternalServerError),
+    };
+    Ok(UrlResponse {
+        url: response.url,
+        seed: response.seed,
+        session: response.session,
+    })
+}
+
+#[post("/v2/generate?<id>&<prompt>&<seed>&<session>&<samples>&<plan>&<cache>")]
+#[allow(clippy::too_many_arguments)]
+async fn generate_v2(
+    server: &State<Server>,
+    account: Account,
+    id: &str,
+    prompt: &str,
+    seed: Option<u32>,
+    session: Option<&str>,
+    samples: Option<usize>,
+    plan: Option<bool>,
+    cache: Option<&str>,
+) -> Result<Json<GenerationResponse>, ApiError> {
+    let query = GenerateQuery {
+        id,
+        prompt,
+        seed,
+        session,
+        samples,
+        plan,
+        cache,
+    };
+    generate_model(server, &account, query).await.map(Json)
+}
+
+/// Generates a model for `prompt`. Passing the `session` returned by an earlier generation gives
+/// the model the previous prompts and code as context, like "now add a garden around it".
+/// `samples` completions are generated and the best scoring one is kept. With `plan`, the layout is
+/// planned in a separate request before any code is written, which helps with complex prompts.
+///
+/// Prompts that start a new session are cached, so repeating one returns the earlier generation
+/// under its original id. Without a `seed`, a cached generation with any seed is returned.
+/// `cache=bypass` skips the lookup and replaces the cached generation with a new one.
+///
+/// `id` may only contain letters, digits, `-` and `_`, and must not end like an edited version,
+/// such as `-v2`.
+async fn generate_model(
+    server: &Server,
+    account: &Account,
+    query: GenerateQuery<'_>,
+) -> Result<GenerationResponse, ApiError> {
+    let GenerateQuery {
+        id,
+        prompt,
+        seed,
+        session,
+        samples,
+        plan,
+        cache,
+    } = query;
     let start = Instant::now();
-    let schem = match nlp::build(&server.openai_api_key, prompt).await {
-        Ok(s) => {
+    check_id(id)?;
+    if edited_version(id) {
+        return Err(Status::BadRequest.into());
+    }
+    let samples = check_samples(samples)?;
+    let plan = plan.unwrap_or(false);
+    let prompts = current_prompts(server);
+    let bypass = match cache {
+        None => false,
+        Some("bypass") => true,
+        Some(_) => return Err(Status::BadRequest.into()),
+    };
+
+    // Only a new session is cached, since earlier turns change what a prompt produces
+    let cache_key = session.is_none().then(|| {
+        CacheKey::new(
+            prompt,
+            nlp::MODEL,
+            prompt_version(server, &prompts),
+            seed,
+            samples,
+            plan,
+        )
+    });
+    if let Some(cached) = cache_key
+        .as_ref()
+        .filter(|_| !bypass)
+        .and_then(|key| server.cache.get(key))
+    {
+        tracing::info!("returning cached generation {}", cached.id);
+        return Ok(cached_response(server, &prompts, prompt, cached));
+    }
+    let seed = seed.unwrap_or_else(rand::random);
+
+    let mut embedding_usage = Usage::default();
+    let embedding = match server.examples.selector() {
+        Selector::Similar => match server
+            .search
+            .embedder()
+            .embed(prompt, &mut embedding_usage)
+            .await
+        {
+            Ok(embedding) => Some(embedding),
+            Err(e) => {
+                tracing::warn!("failed to embed prompt, selecting examples by tags: {}", e);
+                None
+            }
+        },
+        _ => None,
+    };
+    record_embedding_usage(server, account, id, embedding_usage).await;
+    let examples = server.examples.select(prompt, embedding.as_deref(), seed);
+    tracing::info!(
+        "using examples {:?}",
+        examples.iter().map(|e| &e.name).collect::<Vec<_>>()
+    );
+
+    let (session_id, mut session) = match session {
+        Some(session_id) => match server.sessions.get(session_id) {
+            Some(s) => (session_id.to_owned(), s),
+            None => return Err(Status::NotFound.into()),
+        },
+        None => (
+            format!("{:016x}", rand::random::<u64>()),
+            Session::default(),
+        ),
+    };
+
+    let mut usage = Usage::default();
+    let result = nlp::build(
+        &server.openai_api_key,
+        &prompts,
+        &mut session,
+        prompt,
+        seed,
+        samples,
+        plan,
+        &examples,
+        &mut usage,
+    )
+    .await;
+    // Failed code is kept in the session so that the next prompt can ask for a fix
+    server.sessions.insert(session_id.clone(), session);
+    let cost = record_usage(server, account, id, usage).await;
+
+    let generation = match result {
+        Ok(g) => {
             tracing::info!("built after {:?}", start.elapsed());
-            s
+            g
         }
         Err(e) => {
             tracing::error!("failed to generate build: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    let record = GenerationRecord {
+        prompt: prompt.to_owned(),
+        plan: generation.plan.clone(),
+        edits: Vec::new(),
+        code: generation.code.clone(),
+        seed,
+        root: id.to_owned(),
+        version: 1,
+        usage,
+        cost,
+        prompt_version: Some(prompts.version.clone()),
+    };
+    let record_data = serialize_record(&record)?;
+    if let Err(e) = server.object_storage.put_record(id, &record_data).await {
+        tracing::error!("failed to store record: {}", e);
+        return Err(Status::InternalServerError.into());
+    }
+    let mut response = store(server, account, id, &generation, &record, embedding).await?;
+    response.session = Some(session_id);
+    if let Some(key) = cache_key {
+        server.cache.insert(
+            key,
+            CachedGeneration {
+                id: response.id.clone(),
+                url: response.url.clone(),
+                code: record.code,
+                seed,
+                plan: record.plan,
+            },
+        );
+    }
+    Ok(response)
+}
+
+fn current_prompts(server: &Server) -> Arc<Prompts> {
+    server.prompts.read().unwrap().clone()
+}
+
+/// Changes whenever the instructions or examples given to the model change
+fn prompt_version(server: &Server, prompts: &Prompts) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (prompts.fingerprint(), server.examples.version()).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Answers a generation request from the cache. The response starts a new session, as if the
+/// cached code had been generated for it.
+fn cached_response(
+    server: &Server,
+    prompts: &Prompts,
+    prompt: &str,
+    cached: CachedGeneration,
+) -> GenerationResponse {
+    let mut session = Session::default();
+    session.push_turn(prompt, &cached.code);
+    let session_id = format!("{:016x}", rand::random::<u64>());
+    server.sessions.insert(session_id.clone(), session);
+
+    GenerationResponse {
+        id: cached.id,
+        url: cached.url,
+        seed: cached.seed,
+        session: Some(session_id),
+        scores: Vec::new(),
+        plan: cached.plan,
+        usage: Usage::default(),
+        cost: None,
+        cached: true,
+        prompt_version: Some(prompts.version.clone()),
+    }
+}
+
+/// Creates a new version of a generation by asking for changes to its code
+#[post("/generations/<id>/edit?<prompt>&<seed>&<samples>")]
+async fn edit(
+    server: &State<Server>,
+    account: Account,
+    id: &str,
+    prompt: &str,
+    seed: Option<u32>,
+    samples: Option<usize>,
+) -> Result<Json<GenerationResponse>, ApiError> {
+    let start = Instant::now();
+    check_id(id)?;
+    let samples = check_samples(samples)?;
+    let record = match load_record(server, id).await? {
+        Some(r) => r,
+        None => return Err(Status::NotFound.into()),
+    };
+
+    let seed = seed.unwrap_or(record.seed);
+    let prompts = current_prompts(server);
+    let mut usage = Usage::default();
+    let result = nlp::edit(
+        &server.openai_api_key,
+        &prompts,
+        &record.prompt,
+        &record.code,
+        prompt,
+        seed,
+        samples,
+        &mut usage,
+    )
+    .await;
+    let cost = record_usage(server, &account, id, usage).await;
+    let generation = match result {
+        Ok(g) => {
+            tracing::info!("edited after {:?}", start.elapsed());
+            g
+        }
+        Err(e) => {
+            tracing::error!("failed to edit build: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    let mut edits = record.edits;
+    edits.push(prompt.to_owned());
+    let mut new_record = GenerationRecord {
+        prompt: record.prompt,
+        // The edited code no longer follows the plan of the original
+        plan: None,
+        edits,
+        code: generation.code.clone(),
+        seed,
+        root: record.root,
+        version: record.version + 1,
+        usage,
+        cost,
+        prompt_version: Some(prompts.version.clone()),
+    };
+
+    // Editing an older version must not overwrite versions created from it before, including
+    // ones created by concurrent edits, so the first free version is claimed by creating it
+    let new_id = loop {
+        let new_id = format!("{}-v{}", new_record.root, new_record.version);
+        match server
+            .object_storage
+            .create_record(&new_id, &serialize_record(&new_record)?)
+            .await
+        {
+            Ok(true) => break new_id,
+            Ok(false) => new_record.version += 1,
+            Err(e) => {
+                tracing::error!("failed to store record: {}", e);
+                return Err(Status::InternalServerError.into());
+            }
+        }
+    };
+    Ok(Json(
+        store(server, &account, &new_id, &generation, &new_record, None).await?,
+    ))
+}
+
+/// Longest id of a generation
+const MAX_ID_LENGTH: usize = 64;
+
+/// Ids are used as storage keys. Records that aren't generations, like the usage ledger, are
+/// stored under prefixes such as `usage/`, which ids can't reach without a `/`.
+fn check_id(id: &str) -> Result<(), Status> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Status::BadRequest)
+    }
+}
+
+/// Whether `id` has the form `<root>-v<version>` of an edited version
+fn edited_version(id: &str) -> bool {
+    match id.rsplit_once("-v") {
+        Some((root, version)) => {
+            !root.is_empty() && !version.is_empty() && version.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Defaults to a single sample
+fn check_samples(samples: Option<usize>) -> Result<usize, Status> {
+    match samples.unwrap_or(1) {
+        samples @ 1..=nlp::MAX_SAMPLES => Ok(samples),
+        _ => Err(Status::BadRequest),
+    }
+}
+
+async fn load_record(server: &Server, id: &str) -> Result<Option<GenerationRecord>, Status> {
+    let data = match server.object_storage.get_record(id).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            tracing::error!("failed to load record: {}", e);
             return Err(Status::InternalServerError);
         }
     };
 
+    match serde_json::from_slice(&data) {
+        Ok(record) => Ok(Some(record)),
+        Err(e) => {
+            tracing::error!("failed to deserialize record {}: {}", id, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+fn serialize_record(record: &GenerationRecord) -> Result<Vec<u8>, Status> {
+    serde_json::to_vec(record).map_err(|e| {
+        tracing::error!("failed to serialize record: {}", e);
+        Status::InternalServerError
+    })
+}
+
+/// Logs the tokens spent on a generation, whether it succeeded or not, and returns their cost.
+/// Failing to log them doesn't fail the request, as the tokens were spent either way.
+async fn record_usage(server: &Server, account: &Account, id: &str, usage: Usage) -> Option<f64> {
+    let cost = server.prices.cost(nlp::MODEL, &usage);
+    let entry = UsageEntry::new(
+        &account.0,
+        id,
+        UsageKind::Generation,
+        nlp::MODEL,
+        usage,
+        cost,
+    );
+    log_usage(server, entry).await;
+    cost
+}
+
+/// Logs the tokens spent on embeddings, unless nothing was embedded. `id` is empty for searches.
+async fn record_embedding_usage(server: &Server, account: &Account, id: &str, usage: Usage) {
+    if usage.total_tokens == 0 {
+        return;
+    }
+    let model = server.search.embedder().model();
+    let cost = server.prices.cost(model, &usage);
+    let entry = UsageEntry::new(&account.0, id, UsageKind::Embedding, model, usage, cost);
+    log_usage(server, entry).await;
+}
+
+async fn log_usage(server: &Server, entry: UsageEntry) {
+    let id = entry.generation.clone();
+    if let Err(e) = server
+        .ledger
+        .record(server.object_storage.as_ref(), entry)
+        .await
+    {
+        tracing::error!("failed to record usage of {:?}: {}", id, e);
+    }
+}
+
+/// Stores the serialized model of a generation whose record is already stored. `embedding` is the
+/// embedding of the prompt if selecting examples already needed it, so it isn't requested twice.
+async fn store(
+    server: &Server,
+    account: &Account,
+    id: &str,
+    generation: &Generation,
+    record: &GenerationRecord,
+    embedding: Option<Vec<f32>>,
+) -> Result<GenerationResponse, Status> {
     let mut data = Vec::with_capacity(256);
-    match schem.serialize(&mut data) {
-        Ok(_) => tracing::info!("serialized after {:?}", start.elapsed()),
+    match generation.model.serialize(&mut data) {
+        Ok(_) => tracing::info!("serialized {}", id),
         Err(e) => {
             tracing::error!("failed to serialize build: {}", e);
             return Err(Status::InternalServerError);
         }
     }
 
-    match server.object_storage.put(id, &data).await {
-        Ok(loc) => Ok(loc),
+    let url = match server.object_storage.put(id, &data).await {
+        Ok(url) => url,
         Err(e) => {
             tracing::error!("failed to store build: {}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    // Edits are found by the original prompt as well as their instructions
+    let description = std::iter::once(&record.prompt)
+        .chain(&record.edits)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut embedding_usage = Usage::default();
+    if let Err(e) = server
+        .search
+        .insert(
+            server.object_storage.as_ref(),
+            &account.0,
+            id,
+            &description,
+            embedding.filter(|_| record.edits.is_empty()),
+            &url,
+            &record.code,
+            &mut embedding_usage,
+        )
+        .await
+    {
+        tracing::error!("failed to index {}: {}", id, e);
+    }
+    record_embedding_usage(server, account, id, embedding_usage).await;
+
+    Ok(GenerationResponse {
+        id: id.to_owned(),
+        url,
+        seed: record.seed,
+        session: None,
+        scores: generation.scores.clone(),
+        plan: record.plan.clone(),
+        usage: record.usage,
+        cost: record.cost,
+        cached: false,
+        prompt_version: record.prompt_version.clone(),
+    })
+}
+
+/// Longest range accepted by `/usage`
+const MAX_USAGE_DAYS: u64 = 366;
+
+#[derive(Serialize)]
+struct UsageResponse {
+    from: u64,
+    to: u64,
+    total: UsageSummary,
+    /// Usage per account
+    accounts: BTreeMap<String, UsageSummary>,
+}
+
+/// Token usage and cost between `from` (inclusive) and `to` (exclusive), given in unix seconds.
+/// Defaults to the last 30 days. With `account`, only that account is counted. Admin only.
+#[get("/usage?<from>&<to>&<account>")]
+async fn get_usage(
+    server: &State<Server>,
+    _admin: Admin,
+    from: Option<u64>,
+    to: Option<u64>,
+    account: Option<&str>,
+) -> Result<Json<UsageResponse>, Status> {
+    let to = to.unwrap_or_else(|| usage::now() + 1);
+    let from = from.unwrap_or_else(|| to.saturating_sub(30 * 24 * 60 * 60));
+    if from > to || to - from > MAX_USAGE_DAYS * 24 * 60 * 60 {
+        return Err(Status::BadRequest);
+    }
+
+    let mut entries = match server
+        .ledger
+        .entries(server.object_storage.as_ref(), from, to)
+        .await
+    {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::error!("failed to load usage: {}", e);
+            return Err(Status::InternalServerError);
+        }
+    };
+    if let Some(account) = account {
+        entries.retain(|e| e.account == account);
+    }
+
+    let (total, accounts) = usage::summarize(&entries);
+    Ok(Json(UsageResponse {
+        from,
+        to,
+        total,
+        accounts,
+    }))
+}
+
+/// Most results returned by `/search`
+const MAX_SEARCH_RESULTS: usize = 50;
+
+#[derive(Serialize)]
+struct SearchResult {
+    id: String,
+    prompt: String,
+    url: String,
+    /// Cosine similarity of the prompts, from -1 to 1
+    similarity: f32,
+}
+
+/// The caller's earlier generations whose prompts are most similar to `q`, most similar first.
+/// Returns 10 results unless `limit` says otherwise. Callers without a key share an account, so
+/// they can't search.
+#[get("/search?<q>&<limit>")]
+async fn search(
+    server: &State<Server>,
+    account: Account,
+    q: &str,
+    limit: Option<usize>,
+) -> Result<Json<Vec<SearchResult>>, Status> {
+    if account.0 == ANONYMOUS {
+        return Err(Status::Unauthorized);
+    }
+    let limit = match limit.unwrap_or(10) {
+        limit @ 1..=MAX_SEARCH_RESULTS => limit,
+        _ => return Err(Status::BadRequest),
+    };
+
+    let mut usage = Usage::default();
+    let result = server
+        .search
+        .nearest(&account.0, q, limit, &mut usage)
+        .await
+        // The error isn't `Send`, so it can't be kept across the await below
+        .map_err(|e| e.to_string());
+    record_embedding_usage(server, &account, "", usage).await;
+    match result {
+        Ok(matches) => Ok(Json(
+            matches
+                .into_iter()
+                .map(|(similarity, entry)| SearchResult {
+                    id: entry.id,
+                    prompt: entry.prompt,
+                    url: entry.url,
+                    similarity,
+                })
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("failed to search for {:?}: {}", q, e);
             Err(Status::InternalServerError)
         }
     }
 }
+
+#[derive(Serialize)]
+struct ReloadResponse {
+    prompt_version: String,
+}
+
+/// Reloads the prompt templates from disk, switching to `version` if given. Generations already
+/// running finish with the prompts they started with. Invalid templates leave the current prompts
+/// in place. Admin only.
+#[post("/prompts/reload?<version>")]
+async fn reload_prompts(
+    server: &State<Server>,
+    _admin: Admin,
+    version: Option<&str>,
+) -> Result<Json<ReloadResponse>, Status> {
+    let prompts = match current_prompts(server).reload(version) {
+        Ok(prompts) => prompts,
+        Err(e) => {
+            tracing::error!("failed to reload prompts: {}", e);
+            return Err(Status::BadRequest);
+        }
+    };
+
+    tracing::info!("loaded prompt version {}", prompts.version);
+    let prompt_version = prompts.version.clone();
+    *server.prompts.write().unwrap() = Arc::new(prompts);
+    Ok(Json(ReloadResponse { prompt_version }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_id, constant_time_eq, edited_version};
+
+    #[test]
+    fn test_check_id() {
+        assert!(check_id("castle_01-a").is_ok());
+        for id in ["", "usage/19000", "../x", "a b", &"a".repeat(65)] {
+            assert!(check_id(id).is_err(), "{}", id);
+        }
+
+        assert!(edited_version("castle-v2"));
+        assert!(!edited_version("castle-v"));
+        assert!(!edited_version("castle-vx"));
+        assert!(!edited_version("-v2"));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+}
@@ -1,23 +1,33 @@
+use reqwest::header::{HeaderMap, HeaderValue, IF_NONE_MATCH};
 use rocket::async_trait;
 
 use super::ObjectStorage;
 
 pub struct CloudflareR2Storage {
     bucket: s3::Bucket,
+    /// Private bucket for records, which contain prompts and code
+    records: s3::Bucket,
     public_url: String,
 }
 
 impl CloudflareR2Storage {
     pub fn new(
         bucket_name: &str,
+        records_bucket_name: &str,
         account_id: String,
         credentials: s3::creds::Credentials,
         public_url: String,
     ) -> Result<CloudflareR2Storage, s3::error::S3Error> {
-        let bucket = s3::Bucket::new(bucket_name, s3::Region::R2 { account_id }, credentials)?
-            .with_path_style();
+        let region = s3::Region::R2 { account_id };
+        let bucket =
+            s3::Bucket::new(bucket_name, region.clone(), credentials.clone())?.with_path_style();
+        let records = s3::Bucket::new(records_bucket_name, region, credentials)?.with_path_style();
 
-        Ok(CloudflareR2Storage { bucket, public_url })
+        Ok(CloudflareR2Storage {
+            bucket,
+            records,
+            public_url,
+        })
     }
 }
 
@@ -28,4 +38,47 @@ impl ObjectStorage for CloudflareR2Storage {
         self.bucket.put_object(file.clone(), data).await?;
         Ok(format!("{}/{}", self.public_url, file))
     }
+
+    async fn put_record(&self, id: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.records
+            .put_object(format!("{}.json", id), data)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_record(
+        &self,
+        id: &str,
+        data: &[u8],
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // R2 rejects the upload with 412 Precondition Failed if the object exists
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+        let bucket = self.records.with_extra_headers(headers);
+        match bucket.put_object(format!("{}.json", id), data).await {
+            Ok(_) => Ok(true),
+            Err(s3::error::S3Error::Http(412, _)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list_records(&self, prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let pages = self
+            .records
+            .list(prefix.to_owned(), Some("/".to_owned()))
+            .await?;
+        Ok(pages
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .filter_map(|object| object.key.strip_suffix(".json").map(str::to_owned))
+            .collect())
+    }
+
+    async fn get_record(&self, id: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match self.records.get_object(format!("{}.json", id)).await {
+            Ok(response) => Ok(Some(response.to_vec())),
+            Err(s3::error::S3Error::Http(404, _)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 }
@@ -1,9 +1,23 @@
+use std::io::Write;
+use std::path::PathBuf;
+
 use rocket::async_trait;
 
 use super::ObjectStorage;
 
+/// Directory that records are kept in, next to the models
+const RECORDS_DIR: &str = "records";
+
 pub struct FileSystemStorage;
 
+fn record_path(id: &str) -> Result<PathBuf, std::io::Error> {
+    let path = PathBuf::from(RECORDS_DIR).join(format!("{}.json", id));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
 #[async_trait]
 impl ObjectStorage for FileSystemStorage {
     async fn put(&self, id: &str, data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
@@ -11,4 +25,53 @@ impl ObjectStorage for FileSystemStorage {
         std::fs::write(&path, data)?;
         Ok(path)
     }
+
+    async fn put_record(&self, id: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(record_path(id)?, data)?;
+        Ok(())
+    }
+
+    async fn create_record(
+        &self,
+        id: &str,
+        data: &[u8],
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(record_path(id)?);
+        match file {
+            Ok(mut file) => {
+                file.write_all(data)?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list_records(&self, prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let entries = match std::fs::read_dir(PathBuf::from(RECORDS_DIR).join(prefix)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut ids = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            if let Some(id) = name.to_str().and_then(|n| n.strip_suffix(".json")) {
+                ids.push(format!("{}{}", prefix, id));
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn get_record(&self, id: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match std::fs::read(record_path(id)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 }
@@ -2,5 +2,25 @@ use rocket::async_trait;
 
 #[async_trait]
 pub trait ObjectStorage: Send + Sync {
+    /// Stores a serialized model where clients can download it and returns its URL
     async fn put(&self, id: &str, data: &[u8]) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Stores what is needed to rebuild a generation, such as its Lua source. Records are kept
+    /// apart from models and are never public.
+    async fn put_record(&self, id: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Like `put_record`, but only stores the record if there is none under `id` yet. Returns
+    /// whether it was stored.
+    async fn create_record(
+        &self,
+        id: &str,
+        data: &[u8],
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Ids of every record under `prefix`, which ends with a `/`. Records in nested prefixes are
+    /// not included.
+    async fn list_records(&self, prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+
+    /// Returns `None` if no record is stored under `id`
+    async fn get_record(&self, id: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>>;
 }
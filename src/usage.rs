@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::nlp::Usage;
+use crate::storage::ObjectStorage;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Price of a model in USD per 1000 tokens
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Price {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+/// Prices by model name, like `{"gpt-4": {"prompt": 0.03, "completion": 0.06}}`
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct PriceTable(HashMap<String, Price>);
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        PriceTable(HashMap::from([
+            (
+                "gpt-4".to_owned(),
+                Price {
+                    prompt: 0.03,
+                    completion: 0.06,
+                },
+            ),
+            (
+                "text-embedding-ada-002".to_owned(),
+                Price {
+                    prompt: 0.0001,
+                    completion: 0.,
+                },
+            ),
+        ]))
+    }
+}
+
+impl PriceTable {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Cost in USD, or `None` if the model has no price
+    pub fn cost(&self, model: &str, usage: &Usage) -> Option<f64> {
+        let price = self.0.get(model)?;
+        Some(
+            (usage.prompt_tokens as f64 * price.prompt
+                + usage.completion_tokens as f64 * price.completion)
+                / 1000.,
+        )
+    }
+}
+
+/// What tokens were spent on
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageKind {
+    /// Writing the code of a generation or edit
+    #[default]
+    Generation,
+    /// Embedding a prompt to select examples, index a generation or search
+    Embedding,
+}
+
+/// Tokens spent on one generation, edit or embedding
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UsageEntry {
+    /// Unix time in seconds
+    pub time: u64,
+    /// Account of the caller, see `account_id`
+    #[serde(alias = "api_key")]
+    pub account: String,
+    /// Generation the tokens were spent on. For edits, this is the version that was edited.
+    /// Empty for searches.
+    pub generation: String,
+    /// Entries logged before embeddings were counted are all generations
+    #[serde(default)]
+    pub kind: UsageKind,
+    pub model: String,
+    pub usage: Usage,
+    pub cost: Option<f64>,
+}
+
+impl UsageEntry {
+    pub fn new(
+        account: &str,
+        generation: &str,
+        kind: UsageKind,
+        model: &str,
+        usage: Usage,
+        cost: Option<f64>,
+    ) -> Self {
+        UsageEntry {
+            time: now(),
+            account: account.to_owned(),
+            generation: generation.to_owned(),
+            kind,
+            model: model.to_owned(),
+            usage,
+            cost,
+        }
+    }
+}
+
+/// Usage log kept in object storage. Entries are grouped in one record per day, so a range query
+/// only reads the days it covers.
+#[derive(Default)]
+pub struct Ledger {
+    /// Serializes the read-modify-write of the daily records
+    lock: Mutex<()>,
+}
+
+impl Ledger {
+    pub async fn record(
+        &self,
+        storage: &dyn ObjectStorage,
+        entry: UsageEntry,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = self.lock.lock().await;
+        let id = day_record(entry.time / SECONDS_PER_DAY);
+        let mut entries = load_day(storage, &id).await?;
+        entries.push(entry);
+        storage
+            .put_record(&id, &serde_json::to_vec(&entries)?)
+            .await
+    }
+
+    /// Entries with `from <= time < to`, oldest first
+    pub async fn entries(
+        &self,
+        storage: &dyn ObjectStorage,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<UsageEntry>, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        if from >= to {
+            return Ok(entries);
+        }
+        for day in from / SECONDS_PER_DAY..=(to - 1) / SECONDS_PER_DAY {
+            entries.extend(
+                load_day(storage, &day_record(day))
+                    .await?
+                    .into_iter()
+                    .filter(|e| (from..to).contains(&e.time)),
+            );
+        }
+        Ok(entries)
+    }
+}
+
+/// Kept under a prefix of its own, apart from generations
+fn day_record(day: u64) -> String {
+    format!("usage/{}", day)
+}
+
+async fn load_day(
+    storage: &dyn ObjectStorage,
+    id: &str,
+) -> Result<Vec<UsageEntry>, Box<dyn std::error::Error>> {
+    match storage.get_record(id).await? {
+        Some(data) => Ok(serde_json::from_slice(&data)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct UsageSummary {
+    pub generations: u32,
+    pub embeddings: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    /// Cost in USD of the entries whose model has a price
+    pub cost: f64,
+}
+
+impl UsageSummary {
+    fn add(&mut self, entry: &UsageEntry) {
+        match entry.kind {
+            UsageKind::Generation => self.generations += 1,
+            UsageKind::Embedding => self.embeddings += 1,
+        }
+        self.prompt_tokens += entry.usage.prompt_tokens as u64;
+        self.completion_tokens += entry.usage.completion_tokens as u64;
+        self.total_tokens += entry.usage.total_tokens as u64;
+        self.cost += entry.cost.unwrap_or(0.);
+    }
+}
+
+/// Totals over all entries and per account
+pub fn summarize(entries: &[UsageEntry]) -> (UsageSummary, BTreeMap<String, UsageSummary>) {
+    let mut total = UsageSummary::default();
+    let mut accounts = BTreeMap::<String, UsageSummary>::new();
+    for entry in entries {
+        total.add(entry);
+        accounts
+            .entry(entry.account.clone())
+            .or_default()
+            .add(entry);
+    }
+    (total, accounts)
+}
+
+/// Opaque id of the account an API key belongs to, so keys are never stored or shown. Every key
+/// is an account of its own, and only callers that know a key can spend on its account.
+pub fn account_id(api_key: &str) -> String {
+    let hash = Sha256::digest(api_key.as_bytes());
+    hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nlp::Usage;
+
+    use super::{account_id, summarize, PriceTable, UsageEntry, UsageKind};
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> Usage {
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    #[test]
+    fn test_cost() {
+        let prices = PriceTable::default();
+        let cost = prices.cost("gpt-4", &usage(1000, 500)).unwrap();
+        assert!((cost - 0.06).abs() < 1e-9);
+        assert_eq!(prices.cost("davinci", &usage(1000, 500)), None);
+        assert!(
+            prices
+                .cost("text-embedding-ada-002", &usage(1000, 0))
+                .unwrap()
+                > 0.
+        );
+
+        let prices =
+            PriceTable::from_json(r#"{"davinci": {"prompt": 0.02, "completion": 0.02}}"#).unwrap();
+        assert!(prices.cost("davinci", &usage(1000, 0)).is_some());
+        assert!(prices.cost("gpt-4", &usage(1000, 0)).is_none());
+    }
+
+    #[test]
+    fn test_summarize() {
+        const G: UsageKind = UsageKind::Generation;
+        let entries = [
+            UsageEntry::new("a", "house", G, "gpt-4", usage(100, 50), Some(0.006)),
+            UsageEntry::new("b", "tree", G, "gpt-4", usage(200, 100), Some(0.012)),
+            UsageEntry::new("a", "house-v2", G, "other", usage(10, 10), None),
+            UsageEntry::new("b", "", UsageKind::Embedding, "other", usage(5, 0), None),
+        ];
+        let (total, accounts) = summarize(&entries);
+        assert_eq!(total.generations, 3);
+        assert_eq!(total.embeddings, 1);
+        assert_eq!(total.total_tokens, 475);
+        assert!((total.cost - 0.018).abs() < 1e-9);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts["a"].generations, 2);
+        assert_eq!(accounts["a"].prompt_tokens, 110);
+    }
+
+    #[test]
+    fn test_account_id() {
+        let id = account_id("sk-secret");
+        assert_eq!(id.len(), 16);
+        assert!(!id.contains("secret"));
+        assert_eq!(id, account_id("sk-secret"));
+        assert_ne!(id, account_id("sk-secret2"));
+    }
+}